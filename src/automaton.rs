@@ -0,0 +1,227 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::{SymbolId, SymbolTable, Grammar, Item, BitSet, LrItem, State};
+
+/// The canonical collection of LR(1) item sets.
+///
+/// Only needed to build a `Table` and for diagnostics; parsing runs on the
+/// compiled table alone.
+#[derive(Debug, Clone)]
+pub struct Automaton {
+    pub states: Vec<State>,
+    state_index: HashMap<Vec<LrItem>, usize>,
+
+    // For every production and stacktop, FIRST of the symbols after the
+    // stacktop and whether they can all derive LAMBDA.
+    first_after: Vec<Vec<(BitSet, bool)>>,
+}
+
+impl Automaton {
+    /// Builds the canonical collection of `g`, whose first production must be
+    /// the fake goal one (see `Grammar::with_fake_goal`).
+    pub fn new(g: &Grammar) -> Automaton {
+        let first_after = g.rules()
+            .iter()
+            .map(|rule| {
+                (0..rule.to.len() + 1)
+                    .map(|stacktop| {
+                        let rest = rule.to.get(stacktop + 1..).unwrap_or(&[]);
+                        g.first_of_ids(rest)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut automaton = Automaton {
+            states: Vec::new(),
+            state_index: HashMap::new(),
+            first_after,
+        };
+
+        automaton.build_cc(g);
+        automaton
+    }
+
+    /// Computes the closure of a kernel, merging the lookaheads of items that
+    /// share the same core.
+    pub fn closure(&self, g: &Grammar, kernel: &[LrItem]) -> Vec<LrItem> {
+        let mut items = kernel.to_vec();
+        let mut index: HashMap<(usize, usize), usize> = items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (item.core(), i))
+            .collect();
+        let mut pending: Vec<usize> = (0..items.len()).collect();
+
+        while let Some(i) = pending.pop() {
+            let (prod, stacktop) = items[i].core();
+            let prods = match g.rules()[prod].to.get(stacktop) {
+                Some(&next) => g.prods_of(next),
+                None => continue,
+            };
+
+            let lookaheads = {
+                let (ref first, nullable) = self.first_after[prod][stacktop];
+                let mut set = first.clone();
+                if nullable {
+                    set.union_with(&items[i].lookaheads);
+                }
+                set
+            };
+
+            for &p in prods {
+                match index.get(&(p, 0)) {
+                    Some(&j) => {
+                        if items[j].lookaheads.union_with(&lookaheads) {
+                            pending.push(j);
+                        }
+                    }
+                    None => {
+                        index.insert((p, 0), items.len());
+                        pending.push(items.len());
+                        items.push(LrItem::new(p, 0, lookaheads.clone()));
+                    }
+                }
+            }
+        }
+
+        items
+    }
+
+    /// Returns the kernel of the state reached from `items` through `x`.
+    pub fn goto(&self, g: &Grammar, items: &[LrItem], x: SymbolId) -> Option<Vec<LrItem>> {
+        let mut next: Vec<LrItem> = items
+            .iter()
+            .filter(|item| g.rules()[item.prod].to.get(item.stacktop) == Some(&x))
+            .map(|item| {
+                LrItem::new(item.prod, item.stacktop + 1, item.lookaheads.clone())
+            })
+            .collect();
+
+        if next.is_empty() {
+            None
+        } else {
+            next.sort();
+            Some(next)
+        }
+    }
+
+    fn add_state(&mut self, kernel: Vec<LrItem>) -> usize {
+        if let Some(&i) = self.state_index.get(&kernel) {
+            return i;
+        }
+
+        let i = self.states.len();
+        self.state_index.insert(kernel.clone(), i);
+        self.states.push(State::new(kernel));
+        i
+    }
+
+    fn build_cc(&mut self, g: &Grammar) {
+        let eof = SymbolTable::eof().index();
+        self.add_state(vec![LrItem::new(0, 0, BitSet::singleton(eof))]);
+
+        let mut i = 0;
+        while i < self.states.len() {
+            let items = self.closure(g, &self.states[i].kernel);
+            let mut successors: BTreeMap<SymbolId, Vec<LrItem>> = BTreeMap::new();
+            let mut reductions = Vec::new();
+
+            for item in items {
+                match g.rules()[item.prod].to.get(item.stacktop) {
+                    None => reductions.push((item.prod, item.lookaheads)),
+                    Some(&x) => {
+                        successors.entry(x).or_default().push(LrItem::new(
+                            item.prod,
+                            item.stacktop + 1,
+                            item.lookaheads,
+                        ));
+                    }
+                }
+            }
+
+            let mut transitions = BTreeMap::new();
+            for (x, mut kernel) in successors {
+                kernel.sort();
+                transitions.insert(x, self.add_state(kernel));
+            }
+
+            reductions.sort();
+            self.states[i].transitions = transitions;
+            self.states[i].reductions = reductions;
+            i += 1;
+        }
+    }
+
+    /// Merges the states that share the same core (their kernels without the
+    /// lookaheads), as an LALR(1) construction would.
+    ///
+    /// Returns the merged automaton and, for every state of this one, the
+    /// merged state it went into. Merged states are numbered by their first
+    /// canonical state.
+    pub fn merge_cores(&self) -> (Automaton, Vec<usize>) {
+        let mut index: HashMap<Vec<(usize, usize)>, usize> = HashMap::new();
+        let state_of: Vec<usize> = self.states
+            .iter()
+            .map(|state| {
+                let core = state.kernel.iter().map(LrItem::core).collect();
+                let next = index.len();
+                *index.entry(core).or_insert(next)
+            })
+            .collect();
+
+        let mut states: Vec<State> = Vec::with_capacity(index.len());
+        for (i, state) in self.states.iter().enumerate() {
+            let transitions = state.transitions
+                .iter()
+                .map(|(&x, &next)| (x, state_of[next]))
+                .collect();
+
+            // Same cores give the same kernel and reduction order, so items
+            // can be merged pairwise.
+            if state_of[i] == states.len() {
+                let mut merged = State::new(state.kernel.clone());
+                merged.transitions = transitions;
+                merged.reductions = state.reductions.clone();
+                states.push(merged);
+            } else {
+                let merged = &mut states[state_of[i]];
+                for (item, other) in merged.kernel.iter_mut().zip(&state.kernel) {
+                    item.lookaheads.union_with(&other.lookaheads);
+                }
+                for (reduction, other) in merged.reductions.iter_mut().zip(&state.reductions) {
+                    reduction.1.union_with(&other.1);
+                }
+            }
+        }
+
+        let state_index = states
+            .iter()
+            .enumerate()
+            .map(|(i, state)| (state.kernel.clone(), i))
+            .collect();
+        let merged = Automaton {
+            states,
+            state_index,
+            first_after: self.first_after.clone(),
+        };
+        (merged, state_of)
+    }
+
+    /// Expands items with lookahead sets into one `Item` per lookahead.
+    pub fn expand(&self, items: &[LrItem]) -> BTreeSet<Item> {
+        items
+            .iter()
+            .flat_map(|item| {
+                item.lookaheads.iter().map(move |la| {
+                    Item::new(item.prod, item.stacktop, SymbolId(la as u32))
+                })
+            })
+            .collect()
+    }
+
+    /// The full item set (closure of the kernel) of the state `i`.
+    pub fn state_items(&self, g: &Grammar, i: usize) -> BTreeSet<Item> {
+        self.expand(&self.closure(g, &self.states[i].kernel))
+    }
+}
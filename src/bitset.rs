@@ -0,0 +1,110 @@
+use std::fmt;
+
+const WORD: usize = 64;
+
+/// A growable set of small integers backed by a vector of words.
+///
+/// Used for lookahead sets and FIRST sets, where the members are terminal
+/// indices and the sets are unioned over and over while building the
+/// canonical collection.
+#[derive(Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new() -> BitSet {
+        BitSet { words: Vec::new() }
+    }
+
+    pub fn singleton(i: usize) -> BitSet {
+        let mut set = BitSet::new();
+        set.insert(i);
+        set
+    }
+
+    /// Returns `true` if `i` was not already in the set.
+    pub fn insert(&mut self, i: usize) -> bool {
+        let (word, bit) = (i / WORD, i % WORD);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let old = self.words[word];
+        self.words[word] |= 1 << bit;
+        old != self.words[word]
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.words
+            .get(i / WORD)
+            .map(|w| w & (1 << (i % WORD)) != 0)
+            .unwrap_or(false)
+    }
+
+    /// Adds every member of `other`, returning `true` if the set changed.
+    pub fn union_with(&mut self, other: &BitSet) -> bool {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+
+        let mut changed = false;
+        for (w, o) in self.words.iter_mut().zip(&other.words) {
+            let old = *w;
+            *w |= *o;
+            changed |= old != *w;
+        }
+
+        // Keep the representation canonical so derived `Eq` and `Hash` agree
+        // with set equality.
+        while self.words.last() == Some(&0) {
+            self.words.pop();
+        }
+        changed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            words: &self.words,
+            word: 0,
+            current: self.words.first().cloned().unwrap_or(0),
+        }
+    }
+}
+
+pub struct Iter<'a> {
+    words: &'a [u64],
+    word: usize,
+    current: u64,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.current == 0 {
+            self.word += 1;
+            if self.word >= self.words.len() {
+                return None;
+            }
+            self.current = self.words[self.word];
+        }
+
+        let bit = self.current.trailing_zeros() as usize;
+        self.current &= self.current - 1;
+        Some(self.word * WORD + bit)
+    }
+}
+
+impl fmt::Debug for BitSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
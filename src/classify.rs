@@ -0,0 +1,345 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::{ActionEntry, Automaton, BitSet, Conflict, Grammar, LalrReport, Symbol, SymbolId, SymbolTable,
+            Table};
+
+/// The grammar classes reported by `Grammar::classify`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GrammarClass {
+    Ll1,
+    Lr0,
+    Slr1,
+    Lalr1,
+    Lr1,
+}
+
+impl fmt::Display for GrammarClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GrammarClass::Ll1 => write!(f, "LL(1)"),
+            GrammarClass::Lr0 => write!(f, "LR(0)"),
+            GrammarClass::Slr1 => write!(f, "SLR(1)"),
+            GrammarClass::Lalr1 => write!(f, "LALR(1)"),
+            GrammarClass::Lr1 => write!(f, "LR(1)"),
+        }
+    }
+}
+
+/// Which classes a grammar belongs to, with the first reason it fails each
+/// of the others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Classification {
+    /// Every class in order, with `None` if the grammar belongs to it.
+    pub results: Vec<(GrammarClass, Option<String>)>,
+}
+
+impl Classification {
+    pub fn is(&self, class: GrammarClass) -> bool {
+        self.failure(class).is_none()
+    }
+
+    /// Why the grammar is not in `class`, if it is not.
+    pub fn failure(&self, class: GrammarClass) -> Option<&str> {
+        self.results
+            .iter()
+            .find(|result| result.0 == class)
+            .and_then(|result| result.1.as_deref())
+    }
+
+    /// The classes the grammar belongs to.
+    pub fn classes(&self) -> Vec<GrammarClass> {
+        self.results
+            .iter()
+            .filter(|result| result.1.is_none())
+            .map(|result| result.0)
+            .collect()
+    }
+}
+
+impl fmt::Display for Classification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &(class, ref failure) in &self.results {
+            match *failure {
+                None => writeln!(f, "{}: yes", class)?,
+                Some(ref reason) => writeln!(f, "{}: no, {}", class, reason)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Grammar {
+    /// Checks the grammar against every class in `GrammarClass`.
+    ///
+    /// The LR classes are checked on their tables, so conflicts resolved by
+    /// precedence declarations do not count. LR(0) and SLR(1) states accept
+    /// on EOF only.
+    pub fn classify(&self) -> Classification {
+        let g = self.with_fake_goal();
+        let follow = follow(&g);
+        let automaton = Automaton::new(&g);
+        let (lr0, _) = automaton.merge_cores();
+
+        let all = input_terminals(&g).into_iter().fold(BitSet::new(), |mut set, t| {
+            set.insert(t.index());
+            set
+        });
+        let lr0_table = with_lookaheads(&g, &lr0, |_| all.clone());
+        let slr_table = with_lookaheads(&g, &lr0, |from| {
+            follow[g.symbols().non_terminal_index(from)].clone()
+        });
+        let lalr = LalrReport::new(&g, &automaton)
+            .conflicts
+            .into_iter()
+            .next()
+            .map(|conflict| conflict.conflict.to_string(&g));
+
+        let results = vec![
+            (GrammarClass::Ll1, ll1_failure(&g, &follow)),
+            (GrammarClass::Lr0, first_conflict(&g, &lr0_table)),
+            (GrammarClass::Slr1, first_conflict(&g, &slr_table)),
+            (GrammarClass::Lalr1, lalr),
+            (GrammarClass::Lr1, first_conflict(&g, &Table::new(&automaton, &g))),
+        ];
+
+        Classification { results }
+    }
+}
+
+// The terminals that can appear in the input: all but LAMBDA.
+fn input_terminals(g: &Grammar) -> Vec<SymbolId> {
+    let lambda = g.symbols().id(&Symbol::lambda());
+    g.symbols()
+        .terminals()
+        .into_iter()
+        .filter(|&t| Some(t) != lambda)
+        .collect()
+}
+
+// FOLLOW of every non terminal, by non terminal index.
+fn follow(g: &Grammar) -> Vec<BitSet> {
+    let symbols = g.symbols();
+    let mut follow = vec![BitSet::new(); symbols.non_terminal_count()];
+    follow[symbols.non_terminal_index(g.rules()[0].from)].insert(SymbolTable::eof().index());
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for rule in g.rules() {
+            for (i, &x) in rule.to.iter().enumerate() {
+                if symbols.is_terminal(x) {
+                    continue;
+                }
+
+                let (mut set, nullable) = g.first_of_ids(&rule.to[i + 1..]);
+                if nullable {
+                    set.union_with(&follow[symbols.non_terminal_index(rule.from)]);
+                }
+                changed |= follow[symbols.non_terminal_index(x)].union_with(&set);
+            }
+        }
+    }
+
+    follow
+}
+
+// The table of the LR(0) automaton with every reduction done on the
+// lookaheads `lookaheads` gives for its non terminal.
+fn with_lookaheads<F>(g: &Grammar, lr0: &Automaton, lookaheads: F) -> Table
+where
+    F: Fn(SymbolId) -> BitSet,
+{
+    let mut automaton = lr0.clone();
+    for state in &mut automaton.states {
+        for reduction in &mut state.reductions {
+            reduction.1 = if reduction.0 == 0 {
+                BitSet::singleton(SymbolTable::eof().index())
+            } else {
+                lookaheads(g.rules()[reduction.0].from)
+            };
+        }
+    }
+    Table::new(&automaton, g)
+}
+
+fn first_conflict(g: &Grammar, table: &Table) -> Option<String> {
+    for state in 0..table.state_count() {
+        for t in input_terminals(g) {
+            if let ActionEntry::Conflict(actions) = table.action(state, t) {
+                return Some(Conflict::new(state, t, actions, BTreeSet::new()).to_string(g));
+            }
+        }
+    }
+    None
+}
+
+// Two productions of the same non terminal predicting the same terminal.
+fn ll1_failure(g: &Grammar, follow: &[BitSet]) -> Option<String> {
+    let symbols = g.symbols();
+    for a in symbols.non_terminals() {
+        let predict: Vec<(usize, BitSet, bool)> = g.prods_of(a)
+            .iter()
+            .map(|&prod| {
+                let (mut set, nullable) = g.first_of_ids(&g.rules()[prod].to);
+                if nullable {
+                    set.union_with(&follow[symbols.non_terminal_index(a)]);
+                }
+                (prod, set, nullable)
+            })
+            .collect();
+
+        for (i, &(p, ref p_set, p_nullable)) in predict.iter().enumerate() {
+            for &(q, ref q_set, q_nullable) in &predict[i + 1..] {
+                if let Some(t) = p_set.iter().find(|&t| q_set.contains(t)) {
+                    let overlap = if p_nullable || q_nullable { "FIRST/FOLLOW" } else { "FIRST/FIRST" };
+                    return Some(format!(
+                        "{} overlap: {} and {} both predict {}",
+                        overlap,
+                        g.productions[p],
+                        g.productions[q],
+                        symbols.symbol(SymbolId(t as u32))
+                    ));
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::LAMBDA;
+    use self::GrammarClass::*;
+
+    #[test]
+    fn lr0_test() {
+        let g = Grammar::from_str(
+            "S",
+            vec!["S"],
+            vec![("S", vec!["(", "S", ")"]), ("S", vec!["x"])],
+        );
+        let classification = g.classify();
+        assert_eq!(classification.classes(), vec![Ll1, Lr0, Slr1, Lalr1, Lr1]);
+        assert_eq!(
+            classification.to_string(),
+            "LL(1): yes\nLR(0): yes\nSLR(1): yes\nLALR(1): yes\nLR(1): yes\n"
+        );
+    }
+
+    #[test]
+    fn lambda_column_test() {
+        // Both empty productions reduce in the first state.
+        let g = Grammar::from_str(
+            "S",
+            vec!["S", "A", "B"],
+            vec![
+                ("S", vec!["A", "a"]),
+                ("S", vec!["B", "b"]),
+                ("A", vec![LAMBDA]),
+                ("B", vec![LAMBDA]),
+            ],
+        );
+        let lr0 = g.classify().failure(Lr0).unwrap().to_string();
+        assert!(lr0.starts_with("reduce/reduce conflict"), "{}", lr0);
+
+        let g = g.with_fake_goal();
+        let lambda = g.symbols().id(&Symbol::lambda()).unwrap();
+        let (lr0, _) = Automaton::new(&g).merge_cores();
+        let table = with_lookaheads(&g, &lr0, |_| {
+            input_terminals(&g).into_iter().fold(BitSet::new(), |mut set, t| {
+                set.insert(t.index());
+                set
+            })
+        });
+        assert!((0..table.state_count()).all(|state| table.actions(state, lambda).is_empty()));
+    }
+
+    #[test]
+    fn slr_test() {
+        let g = Grammar::from_str(
+            "E",
+            vec!["E", "T", "F"],
+            vec![
+                ("E", vec!["E", "+", "T"]),
+                ("E", vec!["T"]),
+                ("T", vec!["T", "*", "F"]),
+                ("T", vec!["F"]),
+                ("F", vec!["(", "E", ")"]),
+                ("F", vec!["id"]),
+            ],
+        );
+        let classification = g.classify();
+        assert_eq!(classification.classes(), vec![Slr1, Lalr1, Lr1]);
+
+        let ll1 = classification.failure(Ll1).unwrap();
+        assert_eq!(
+            ll1,
+            "FIRST/FIRST overlap: NT(\"E\") -> NT(\"E\") T(\"+\") NT(\"T\") and \
+             NT(\"E\") -> NT(\"T\") both predict T(\"(\")"
+        );
+        let lr0 = classification.failure(Lr0).unwrap();
+        assert!(lr0.starts_with("shift/reduce conflict"), "{}", lr0);
+    }
+
+    #[test]
+    fn lalr_test() {
+        // The dragon book's L-values: SLR(1) reduces R -> L on =.
+        let g = Grammar::from_str(
+            "S",
+            vec!["S", "L", "R"],
+            vec![
+                ("S", vec!["L", "=", "R"]),
+                ("S", vec!["R"]),
+                ("L", vec!["*", "R"]),
+                ("L", vec!["id"]),
+                ("R", vec!["L"]),
+            ],
+        );
+        let classification = g.classify();
+        assert_eq!(classification.classes(), vec![Lalr1, Lr1]);
+        let slr = classification.failure(Slr1).unwrap();
+        assert!(slr.starts_with("shift/reduce conflict") && slr.contains("on T(\"=\")"), "{}", slr);
+    }
+
+    #[test]
+    fn lr1_test() {
+        let g = Grammar::from_str(
+            "S",
+            vec!["S", "A", "B"],
+            vec![
+                ("S", vec!["a", "A", "d"]),
+                ("S", vec!["b", "B", "d"]),
+                ("S", vec!["a", "B", "e"]),
+                ("S", vec!["b", "A", "e"]),
+                ("A", vec!["c"]),
+                ("B", vec!["c"]),
+            ],
+        );
+        let classification = g.classify();
+        assert_eq!(classification.classes(), vec![Lr1]);
+        assert!(classification.failure(Lalr1).unwrap().starts_with("reduce/reduce conflict"));
+        assert!(classification.to_string().ends_with("\nLR(1): yes\n"), "{}", classification);
+    }
+
+    #[test]
+    fn ll1_test() {
+        let g = Grammar::from_str(
+            "S",
+            vec!["S", "A"],
+            vec![("S", vec!["A", "a"]), ("A", vec!["a"]), ("A", vec![LAMBDA])],
+        );
+        let classification = g.classify();
+        let ll1 = classification.failure(Ll1).unwrap();
+        assert!(ll1.starts_with("FIRST/FOLLOW overlap"), "{}", ll1);
+        assert!(!classification.is(Lr1));
+
+        let g = Grammar::from_str(
+            "S",
+            vec!["S", "A"],
+            vec![("S", vec!["A", "b"]), ("A", vec!["a", "A"]), ("A", vec![LAMBDA])],
+        );
+        assert!(g.classify().is(Ll1));
+    }
+}
@@ -0,0 +1,647 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::{Action, ActionEntry, Grammar, Parser, SymbolId};
+use super::table::NO_STATE;
+
+/// Generates a standalone Rust module that parses with static tables.
+///
+/// The module exposes `parse`, with the same token input and `Tree` output as
+/// `Parser::parse`, and only needs the runtime types (`Tree`, `TokenLike`,
+/// `Symbol` and `ParseError`) of this crate, not the table construction.
+/// Errors carry the same token index, state and expected terminals as the
+/// ones of `Parser::parse`. Meant to be run from a `build.rs`:
+///
+/// ```ignore
+/// let code = RustCodegen::new(grammar).generate().unwrap();
+/// fs::write(Path::new(&env::var("OUT_DIR").unwrap()).join("parser.rs"), code).unwrap();
+/// ```
+///
+/// and included with `mod parser { include!(concat!(env!("OUT_DIR"), "/parser.rs")); }`.
+/// The output only depends on the grammar, so builds are reproducible.
+pub struct RustCodegen {
+    parser: Parser,
+    runtime: String,
+}
+
+// Numbers per line in the generated arrays.
+const ROW_LEN: usize = 16;
+
+fn write_array(out: &mut String, decl: &str, cells: &[u32]) {
+    writeln!(out, "static {}: [u32; {}] = [", decl, cells.len()).unwrap();
+    for row in cells.chunks(ROW_LEN) {
+        let row: Vec<String> = row.iter()
+            .map(|&cell| if cell == NO_STATE {
+                "NO_STATE".to_string()
+            } else {
+                cell.to_string()
+            })
+            .collect();
+        writeln!(out, "    {},", row.join(", ")).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+}
+
+// A match pattern for sorted terminal ids, with runs of three or more
+// written as ranges.
+fn pattern(terminals: &[usize]) -> String {
+    let mut alternatives = Vec::new();
+    let mut i = 0;
+    while i < terminals.len() {
+        let mut j = i;
+        while j + 1 < terminals.len() && terminals[j + 1] == terminals[j] + 1 {
+            j += 1;
+        }
+
+        if j - i >= 2 {
+            alternatives.push(format!("{}..={}", terminals[i], terminals[j]));
+        } else {
+            alternatives.extend(terminals[i..j + 1].iter().map(|t| t.to_string()));
+        }
+        i = j + 1;
+    }
+
+    alternatives.join(" | ")
+}
+
+impl RustCodegen {
+    pub fn new(g: Grammar) -> RustCodegen {
+        RustCodegen {
+            parser: Parser::new(g),
+            runtime: "lr1".to_string(),
+        }
+    }
+
+    /// Path the generated module imports the runtime types from, `lr1` by
+    /// default.
+    pub fn runtime(mut self, path: &str) -> RustCodegen {
+        self.runtime = path.to_string();
+        self
+    }
+
+    pub fn parser(&self) -> &Parser {
+        &self.parser
+    }
+
+    fn check_conflicts(&self) -> Result<(), String> {
+        if self.parser.table().has_conflicts() {
+            return Err("Cannot generate a parser for a grammar with conflicts".to_string());
+        }
+
+        Ok(())
+    }
+
+    // Header, imports and symbol names shared by both backends.
+    fn write_symbols(&self, out: &mut String) {
+        let g = self.parser.grammar();
+        let symbols = g.symbols();
+
+        writeln!(
+            out,
+            "// Generated by lr1 for the grammar {:016x}. Do not edit.\n",
+            g.fingerprint()
+        ).unwrap();
+        writeln!(out, "use {}::{{NodeId, ParseError, Symbol, TokenLike, Tree}};\n", self.runtime).unwrap();
+
+        writeln!(out, "/// Terminal names, indexed by terminal id.").unwrap();
+        writeln!(out, "pub static TERMINALS: [&str; {}] = [", symbols.terminal_count()).unwrap();
+        for t in symbols.terminals() {
+            writeln!(out, "    {:?},", symbols.symbol(t).as_str()).unwrap();
+        }
+        writeln!(out, "];\n").unwrap();
+
+        writeln!(out, "/// Non terminal names, indexed by non terminal index.").unwrap();
+        writeln!(out, "pub static NON_TERMINALS: [&str; {}] = [", symbols.non_terminal_count()).unwrap();
+        for nt in symbols.non_terminals() {
+            writeln!(out, "    {:?},", symbols.symbol(nt).as_str()).unwrap();
+        }
+        writeln!(out, "];\n").unwrap();
+    }
+
+    fn write_rules(&self, out: &mut String) {
+        let g = self.parser.grammar();
+
+        writeln!(out, "// Left hand side (non terminal index) and length of every production.").unwrap();
+        writeln!(out, "static RULES: [(usize, usize); {}] = [", g.rules().len()).unwrap();
+        for rule in g.rules() {
+            writeln!(out, "    ({}, {}),", g.symbols().non_terminal_index(rule.from), rule.to.len()).unwrap();
+        }
+        writeln!(out, "];\n").unwrap();
+    }
+
+    // The terminals with an action in every state, for error messages.
+    fn write_expected(&self, out: &mut String) {
+        let table = self.parser.table();
+        let symbols = self.parser.grammar().symbols();
+
+        writeln!(out, "// Terminal ids with an action, by state.").unwrap();
+        writeln!(out, "static EXPECTED: [&[usize]; {}] = [", table.state_count()).unwrap();
+        for state in 0..table.state_count() {
+            let terminals: Vec<String> = symbols.terminals()
+                .into_iter()
+                .filter(|&t| !table.actions(state, t).is_empty())
+                .map(|t| t.index().to_string())
+                .collect();
+            writeln!(out, "    &[{}],", terminals.join(", ")).unwrap();
+        }
+        writeln!(out, "];\n").unwrap();
+    }
+
+    fn write_terminal_of(&self, out: &mut String) {
+        let symbols = self.parser.grammar().symbols();
+
+        writeln!(out, "fn terminal_of(kind: &str) -> Option<usize> {{").unwrap();
+        writeln!(out, "    match kind {{").unwrap();
+        for t in symbols.terminals() {
+            writeln!(out, "        {:?} => Some({}),", symbols.symbol(t).as_str(), t.index()).unwrap();
+        }
+        writeln!(out, "        _ => None,").unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}\n").unwrap();
+    }
+
+    fn driver(&self, driver: &str) -> String {
+        let eof = self.parser.grammar().symbols().symbol(SymbolId(0));
+        driver.replace("{EOF}", &format!("{:?}", eof.as_str()))
+    }
+
+    /// Table driven backend. Fails if the grammar is not LR(1).
+    pub fn generate(&self) -> Result<String, String> {
+        self.check_conflicts()?;
+
+        let table = self.parser.table();
+        let symbols = self.parser.grammar().symbols();
+        let terminals = symbols.terminals();
+        let non_terminal_count = symbols.non_terminal_count();
+        let mut out = String::new();
+
+        self.write_symbols(&mut out);
+
+        writeln!(out, "const TERMINAL_COUNT: usize = {};", terminals.len()).unwrap();
+        writeln!(out, "const NON_TERMINAL_COUNT: usize = {};", non_terminal_count).unwrap();
+        writeln!(out, "const NO_STATE: u32 = {};\n", NO_STATE).unwrap();
+
+        self.write_rules(&mut out);
+
+        writeln!(out, "// Action cells by state and terminal id: 0 is an error, 4 accepts,").unwrap();
+        writeln!(out, "// `state << 2 | 1` shifts and `production << 2 | 2` reduces.").unwrap();
+        let action: Vec<u32> = (0..table.state_count())
+            .flat_map(|state| {
+                terminals.iter().map(move |&t| table.packed_action(state, t))
+            })
+            .collect();
+        write_array(&mut out, "ACTION", &action);
+        writeln!(out).unwrap();
+
+        writeln!(out, "// Goto by state and non terminal index, `NO_STATE` if missing.").unwrap();
+        let goto: Vec<u32> = (0..table.state_count())
+            .flat_map(|state| {
+                (0..non_terminal_count).map(move |nt| {
+                    table.goto(state, nt).map(|next| next as u32).unwrap_or(NO_STATE)
+                })
+            })
+            .collect();
+        write_array(&mut out, "GOTO", &goto);
+        writeln!(out).unwrap();
+
+        self.write_expected(&mut out);
+        self.write_terminal_of(&mut out);
+        out.push_str(&self.driver(ERRORS));
+        out.push_str(&self.driver(DRIVER));
+
+        Ok(out)
+    }
+
+    /// Recursive ascent backend: every state becomes a function that
+    /// dispatches on the lookahead and returns once a reduction pops it.
+    ///
+    /// Accepts the same inputs, builds the same trees and fails on the same
+    /// token as `generate`, but recurses once per stack element, so very
+    /// deep inputs need a large thread stack. Fails if the grammar is not
+    /// LR(1).
+    pub fn generate_recursive_ascent(&self) -> Result<String, String> {
+        self.check_conflicts()?;
+
+        let table = self.parser.table();
+        let symbols = self.parser.grammar().symbols();
+        let mut out = String::new();
+
+        self.write_symbols(&mut out);
+        self.write_rules(&mut out);
+        self.write_expected(&mut out);
+        self.write_terminal_of(&mut out);
+        out.push_str(&self.driver(ERRORS));
+        out.push_str(&self.driver(ASCENT_DRIVER));
+
+        for state in 0..table.state_count() {
+            let mut shifts = Vec::new();
+            let mut reductions: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+            let mut accept = None;
+            for t in symbols.terminals() {
+                match table.action(state, t) {
+                    ActionEntry::Action(Action::Shift(next)) => shifts.push((t.index(), next)),
+                    ActionEntry::Action(Action::Reduce(prod)) => {
+                        reductions.entry(prod).or_default().push(t.index())
+                    }
+                    ActionEntry::Action(Action::Accept) => accept = Some(t.index()),
+                    _ => {}
+                }
+            }
+
+            let gotos: Vec<(usize, usize)> = (0..symbols.non_terminal_count())
+                .filter_map(|nt| table.goto(state, nt).map(|next| (nt, next)))
+                .collect();
+
+            writeln!(out).unwrap();
+            if shifts.is_empty() && reductions.is_empty() {
+                // Nothing to unwind: the state can only accept or fail.
+                writeln!(
+                    out,
+                    "fn state_{}(ctx: &mut Context, _: Option<NodeId>) -> Result<Ret, ParseError> {{",
+                    state
+                ).unwrap();
+                writeln!(out, "    match ctx.terminal {{").unwrap();
+                if let Some(t) = accept {
+                    writeln!(out, "        {} => Ok(Ret::Accept),", t).unwrap();
+                }
+                writeln!(out, "        _ => Err(ctx.unexpected({})),", state).unwrap();
+                writeln!(out, "    }}").unwrap();
+                writeln!(out, "}}").unwrap();
+                continue;
+            }
+
+            writeln!(
+                out,
+                "fn state_{}(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {{",
+                state
+            ).unwrap();
+            writeln!(out, "    let ret = match ctx.terminal {{").unwrap();
+            for (t, next) in shifts {
+                writeln!(out, "        {} => {{", t).unwrap();
+                writeln!(out, "            let child = ctx.shift({})?;", next).unwrap();
+                writeln!(out, "            state_{}(ctx, Some(child))?", next).unwrap();
+                writeln!(out, "        }}").unwrap();
+            }
+            for (prod, lookaheads) in reductions {
+                writeln!(
+                    out,
+                    "        {} => Ret::Reduce({}, {}, Vec::new()),",
+                    pattern(&lookaheads),
+                    prod,
+                    self.parser.grammar().rules()[prod].to.len()
+                ).unwrap();
+            }
+            if let Some(t) = accept {
+                writeln!(out, "        {} => return Ok(Ret::Accept),", t).unwrap();
+            }
+            writeln!(out, "        _ => return Err(ctx.unexpected({})),", state).unwrap();
+            writeln!(out, "    }};").unwrap();
+
+            if gotos.is_empty() {
+                writeln!(out, "    ctx.unwind(ret, node, no_goto)").unwrap();
+                writeln!(out, "}}").unwrap();
+                continue;
+            }
+
+            writeln!(out, "    ctx.unwind(ret, node, goto_{})", state).unwrap();
+            writeln!(out, "}}\n").unwrap();
+
+            writeln!(
+                out,
+                "fn goto_{}(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {{",
+                state
+            ).unwrap();
+            writeln!(out, "    match from {{").unwrap();
+            for (nt, next) in gotos {
+                writeln!(out, "        {} => state_{}(ctx, Some(node)),", nt, next).unwrap();
+            }
+            writeln!(out, "        _ => no_goto(ctx, from, node),").unwrap();
+            writeln!(out, "    }}").unwrap();
+            writeln!(out, "}}").unwrap();
+        }
+
+        Ok(out)
+    }
+}
+
+const ERRORS: &str = r#"fn expected(state: usize) -> Vec<String> {
+    EXPECTED[state].iter().map(|&t| TERMINALS[t].to_string()).collect()
+}
+
+fn unexpected(index: usize, word: &dyn TokenLike, state: usize) -> ParseError {
+    ParseError::UnexpectedToken {
+        index,
+        kind: word.kind().clone(),
+        lexeme: word.lexeme().clone(),
+        state,
+        expected: expected(state),
+    }
+}
+
+fn unexpected_end(state: usize) -> ParseError {
+    ParseError::UnexpectedEnd {
+        state,
+        expected: expected(state),
+    }
+}
+
+fn token_terminal(index: usize, word: &dyn TokenLike, state: usize) -> Result<usize, ParseError> {
+    terminal_of(word.kind()).ok_or_else(|| unexpected(index, word, state))
+}
+
+"#;
+
+const DRIVER: &str = r#"pub fn parse<I>(mut tokens: I) -> Result<Tree, ParseError>
+where
+    I: Iterator<Item = Box<dyn TokenLike>>,
+{
+    let mut tree = Tree::new();
+    let mut stack: Vec<(usize, Option<NodeId>)> = vec![(0, None)];
+
+    let mut word = match tokens.next() {
+        Some(word) => word,
+        None => return Ok(tree),
+    };
+
+    if word.kind() == {EOF} {
+        return Ok(tree);
+    }
+
+    let mut index = 0;
+    let mut terminal = token_terminal(index, &*word, 0)?;
+
+    loop {
+        let state = stack[stack.len() - 1].0;
+        let cell = ACTION[state * TERMINAL_COUNT + terminal];
+
+        match cell & 3 {
+            1 => {
+                let next = (cell >> 2) as usize;
+                let node_id = tree.new_node(word);
+                stack.push((next, Some(node_id)));
+
+                word = tokens.next().ok_or_else(|| unexpected_end(next))?;
+                index += 1;
+                terminal = token_terminal(index, &*word, next)?;
+            }
+
+            2 => {
+                let (from, len) = RULES[(cell >> 2) as usize];
+                let new_root = tree.new_node(Symbol::NT(NON_TERMINALS[from].to_string()));
+                tree.set_root(new_root);
+
+                let popped = stack.split_off(stack.len() - len);
+                for (_, child_id) in popped {
+                    tree.append(new_root, child_id.expect("Unexpected EOF"));
+                }
+
+                let state = stack[stack.len() - 1].0;
+                let next = GOTO[state * NON_TERMINAL_COUNT + from];
+                if next == NO_STATE {
+                    return Err(ParseError::Internal("Next state is empty".to_string()));
+                }
+                stack.push((next as usize, Some(new_root)));
+            }
+
+            _ if cell == 4 => return Ok(tree),
+
+            _ => return Err(unexpected(index, &*word, state)),
+        }
+    }
+}
+"#;
+
+const ASCENT_DRIVER: &str = r#"enum Ret {
+    Accept,
+    // Production, stack elements left to pop and the popped nodes, last first.
+    Reduce(usize, usize, Vec<NodeId>),
+}
+
+type Goto = fn(&mut Context, usize, NodeId) -> Result<Ret, ParseError>;
+
+struct Context<'a> {
+    tokens: &'a mut dyn Iterator<Item = Box<dyn TokenLike>>,
+    word: Option<Box<dyn TokenLike>>,
+    // Index of `word` in the input.
+    index: usize,
+    terminal: usize,
+    tree: Tree,
+}
+
+impl<'a> Context<'a> {
+    // Shifts the lookahead, going to `state`, and reads the next one.
+    fn shift(&mut self, state: usize) -> Result<NodeId, ParseError> {
+        let word = self.word.take().expect("Shift without a token");
+        let node_id = self.tree.new_node(word);
+
+        let word = self.tokens.next().ok_or_else(|| unexpected_end(state))?;
+        self.index += 1;
+        self.terminal = token_terminal(self.index, &*word, state)?;
+        self.word = Some(word);
+        Ok(node_id)
+    }
+
+    fn unexpected(&self, state: usize) -> ParseError {
+        unexpected(self.index, &**self.word.as_ref().expect("Error without a token"), state)
+    }
+
+    // Either pops the calling state (`node` is its stack element) or, once
+    // every element of the production is popped, builds the non terminal and
+    // moves to the goto state.
+    fn unwind(&mut self, mut ret: Ret, node: Option<NodeId>, goto: Goto) -> Result<Ret, ParseError> {
+        loop {
+            let (prod, left, mut children) = match ret {
+                Ret::Accept => return Ok(Ret::Accept),
+                Ret::Reduce(prod, left, children) => (prod, left, children),
+            };
+
+            if left > 0 {
+                children.push(node.expect("Unexpected EOF"));
+                return Ok(Ret::Reduce(prod, left - 1, children));
+            }
+
+            let from = RULES[prod].0;
+            let new_root = self.tree.new_node(Symbol::NT(NON_TERMINALS[from].to_string()));
+            self.tree.set_root(new_root);
+            for child_id in children.into_iter().rev() {
+                self.tree.append(new_root, child_id);
+            }
+
+            ret = goto(self, from, new_root)?;
+        }
+    }
+}
+
+fn no_goto(_: &mut Context, _: usize, _: NodeId) -> Result<Ret, ParseError> {
+    Err(ParseError::Internal("Next state is empty".to_string()))
+}
+
+pub fn parse<I>(mut tokens: I) -> Result<Tree, ParseError>
+where
+    I: Iterator<Item = Box<dyn TokenLike>>,
+{
+    let word = match tokens.next() {
+        Some(word) => word,
+        None => return Ok(Tree::new()),
+    };
+
+    if word.kind() == {EOF} {
+        return Ok(Tree::new());
+    }
+
+    let terminal = token_terminal(0, &*word, 0)?;
+    let mut ctx = Context {
+        tokens: &mut tokens,
+        word: Some(word),
+        index: 0,
+        terminal,
+        tree: Tree::new(),
+    };
+
+    match state_0(&mut ctx, None)? {
+        Ret::Accept => Ok(ctx.tree),
+        Ret::Reduce(..) => Err(ParseError::Internal("Reduce Error: empty stack".to_string())),
+    }
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use super::super::{ParseError, Tree, TokenLike, LAMBDA};
+    use super::super::test_util::*;
+
+    mod generated {
+        include!("golden/paren.rs");
+    }
+
+    mod paren_ascent {
+        include!("golden/paren_ascent.rs");
+    }
+
+    mod expr_ascent {
+        include!("golden/expr_ascent.rs");
+    }
+
+    fn expr_grammar() -> Grammar {
+        let non_terminals = vec!["Expr", "Expr'", "Term", "Term'", "Factor"];
+        let prods = vec![
+            ("Expr", vec!["Term", "Expr'"]),
+            ("Expr'", vec!["+", "Term", "Expr'"]),
+            ("Expr'", vec!["-", "Term", "Expr'"]),
+            ("Expr'", vec![LAMBDA]),
+            ("Term", vec!["Factor", "Term'"]),
+            ("Term'", vec!["*", "Factor", "Term'"]),
+            ("Term'", vec!["/", "Factor", "Term'"]),
+            ("Term'", vec![LAMBDA]),
+            ("Factor", vec!["(", "Expr", ")"]),
+            ("Factor", vec!["num"]),
+            ("Factor", vec!["name"]),
+        ];
+
+        Grammar::from_str("Expr", non_terminals, prods)
+    }
+
+    #[test]
+    fn golden_test() {
+        let code = RustCodegen::new(list_grammar()).runtime("crate").generate().unwrap();
+        assert!(
+            code == include_str!("golden/paren.rs"),
+            "Generated code changed, update src/golden/paren.rs:\n{}",
+            code
+        );
+    }
+
+    #[test]
+    fn generated_parse_test() {
+        let parser = Parser::new(list_grammar());
+
+        for case in &["( ) EOF", "( ( ) ) ( ) EOF", "( ( ( ) ) ) ( ) ( ( ) ) EOF", "EOF"] {
+            let expected = parser.parse(lex(case)).unwrap();
+            let actual = generated::parse(lex(case)).unwrap();
+            assert_eq!(actual.to_string(), expected.to_string(), "case {:?}", case);
+        }
+
+        for case in &["( EOF", ") EOF", "( ) ) EOF", "( ) x EOF", "( ( )"] {
+            let expected = parser.parse(lex(case)).err().unwrap();
+            let actual = generated::parse(lex(case)).err().unwrap();
+            assert_eq!(actual, expected, "case {:?}", case);
+        }
+    }
+
+    fn assert_golden(code: &str, golden: &str, path: &str) {
+        assert!(code == golden, "Generated code changed, update {}:\n{}", path, code);
+    }
+
+    // Parses `case` and returns the result and the number of tokens read.
+    fn counted<F>(parse: F, case: &str) -> (Result<String, ParseError>, usize)
+    where
+        F: Fn(Box<dyn Iterator<Item = Box<dyn TokenLike>>>) -> Result<Tree, ParseError>,
+    {
+        let read = Rc::new(Cell::new(0));
+        let counter = read.clone();
+        let tokens = lex(case).inspect(move |_| counter.set(counter.get() + 1));
+        let res = parse(Box::new(tokens)).map(|tree| tree.to_string());
+        (res, read.get())
+    }
+
+    #[test]
+    fn recursive_ascent_golden_test() {
+        let code = RustCodegen::new(list_grammar())
+            .runtime("crate")
+            .generate_recursive_ascent()
+            .unwrap();
+        assert_golden(&code, include_str!("golden/paren_ascent.rs"), "src/golden/paren_ascent.rs");
+
+        let code = RustCodegen::new(expr_grammar())
+            .runtime("crate")
+            .generate_recursive_ascent()
+            .unwrap();
+        assert_golden(&code, include_str!("golden/expr_ascent.rs"), "src/golden/expr_ascent.rs");
+    }
+
+    #[test]
+    fn recursive_ascent_parse_test() {
+        let parser = Parser::new(list_grammar());
+        let cases = [
+            "( ) EOF",
+            "( ( ) ) ( ) EOF",
+            "( ( ( ) ) ) ( ) ( ( ) ) EOF",
+            "EOF",
+            "( EOF",
+            ") EOF",
+            "( ) ) EOF",
+            "( ( ) x ) EOF",
+            "( ( )",
+        ];
+        for case in &cases {
+            let (expected, expected_read) = counted(|tokens| parser.parse(tokens), case);
+            let (actual, actual_read) = counted(paren_ascent::parse, case);
+            assert_eq!(actual, expected, "case {:?}", case);
+            assert_eq!(actual_read, expected_read, "case {:?}", case);
+        }
+
+        let parser = Parser::new(expr_grammar());
+        let cases = [
+            "num EOF",
+            "num + name * ( num - num ) / name EOF",
+            "( ( num ) ) EOF",
+            "num + EOF",
+            "num name EOF",
+            "( num * ) EOF",
+        ];
+        for case in &cases {
+            let (expected, expected_read) = counted(|tokens| parser.parse(tokens), case);
+            let (actual, actual_read) = counted(expr_ascent::parse, case);
+            assert_eq!(actual, expected, "case {:?}", case);
+            assert_eq!(actual_read, expected_read, "case {:?}", case);
+        }
+    }
+
+    #[test]
+    fn conflicts_test() {
+        assert!(RustCodegen::new(sum_grammar()).generate().is_err());
+    }
+}
@@ -0,0 +1,305 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{Action, ActionEntry, SymbolId, Table};
+use super::table::{entry, ERROR, NO_STATE, REDUCE, TAG_BITS, TAG_MASK};
+
+const EMPTY: u32 = u32::MAX;
+
+/// Rows of a sparse matrix overlapped into a single vector (a comb vector).
+///
+/// The entry `(row, col)` lives at `base[row] + col` if `check` at that slot
+/// is `row`; otherwise the row's default applies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Comb {
+    base: Vec<u32>,
+    check: Vec<u32>,
+    value: Vec<u32>,
+}
+
+impl Comb {
+    /// First fit packing, placing the densest rows first.
+    fn new(rows: &[Vec<(usize, u32)>]) -> Comb {
+        let mut order: Vec<usize> = (0..rows.len()).collect();
+        order.sort_by_key(|&r| (Reverse(rows[r].len()), r));
+
+        let mut comb = Comb {
+            base: vec![0; rows.len()],
+            check: Vec::new(),
+            value: Vec::new(),
+        };
+
+        for r in order {
+            let row = &rows[r];
+            if row.is_empty() {
+                continue;
+            }
+
+            let fits = |check: &[u32], base: usize| {
+                row.iter().all(|&(col, _)| {
+                    check.get(base + col).map(|c| *c == EMPTY).unwrap_or(true)
+                })
+            };
+            let base = (0..).find(|&base| fits(&comb.check, base)).unwrap();
+
+            let end = base + row.iter().map(|&(col, _)| col).max().unwrap() + 1;
+            if end > comb.check.len() {
+                comb.check.resize(end, EMPTY);
+                comb.value.resize(end, ERROR);
+            }
+            for &(col, value) in row {
+                comb.check[base + col] = r as u32;
+                comb.value[base + col] = value;
+            }
+            comb.base[r] = base as u32;
+        }
+
+        comb
+    }
+
+    fn get(&self, row: usize, col: usize) -> Option<u32> {
+        let slot = self.base[row] as usize + col;
+        match self.check.get(slot) {
+            Some(&r) if r as usize == row => Some(self.value[slot]),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.base.len() + self.check.len() + self.value.len()
+    }
+}
+
+/// Sizes, in table words, of a dense table and its compressed form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionReport {
+    pub dense_size: usize,
+    pub compressed_size: usize,
+}
+
+impl CompressionReport {
+    /// Compressed size as a fraction of the dense size.
+    pub fn ratio(&self) -> f64 {
+        if self.dense_size == 0 {
+            return 1.0;
+        }
+
+        self.compressed_size as f64 / self.dense_size as f64
+    }
+}
+
+impl fmt::Display for CompressionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "dense {} words, compressed {} words ({:.1}%)",
+            self.dense_size,
+            self.compressed_size,
+            self.ratio() * 100.0
+        )
+    }
+}
+
+/// A `Table` compressed the way yacc does it.
+///
+/// Every state gets a default reduction (its most common reduce) and every
+/// non terminal a default goto (its most common target); the remaining
+/// entries are packed into comb vectors. Lookups return the same entries as
+/// the dense table, except that error cells of a state with a default
+/// reduction return that reduction. The error is then still detected on the
+/// same token, before it is shifted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedTable {
+    state_count: usize,
+    terminal_count: usize,
+    non_terminal_count: usize,
+
+    default_action: Vec<u32>,
+    action: Comb,
+    default_goto: Vec<u32>,
+    goto: Comb,
+    conflicts: Vec<Vec<Action>>,
+}
+
+fn most_common<I: Iterator<Item = u32>>(values: I) -> Option<u32> {
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for value in values {
+        *counts.entry(value).or_insert(0) += 1;
+    }
+
+    // Ties go to the smallest value so the output is deterministic.
+    counts
+        .into_iter()
+        .max_by_key(|&(value, count)| (count, Reverse(value)))
+        .map(|(value, _)| value)
+}
+
+impl CompressedTable {
+    pub fn new(table: &Table) -> CompressedTable {
+        let state_count = table.state_count();
+        let terminal_count = table.terminal_count();
+        let non_terminal_count = table.non_terminal_count();
+
+        let mut default_action = Vec::with_capacity(state_count);
+        let mut action_rows = Vec::with_capacity(state_count);
+        for state in 0..state_count {
+            let row: Vec<(usize, u32)> = (0..terminal_count)
+                .map(|t| (t, table.packed_action(state, SymbolId(t as u32))))
+                .filter(|&(_, packed)| packed != ERROR)
+                .collect();
+
+            let default = most_common(
+                row.iter()
+                    .map(|&(_, packed)| packed)
+                    .filter(|packed| packed & TAG_MASK == REDUCE),
+            ).unwrap_or(ERROR);
+
+            default_action.push(default);
+            action_rows.push(
+                row.into_iter()
+                    .filter(|&(_, packed)| packed != default)
+                    .collect(),
+            );
+        }
+
+        let mut default_goto = Vec::with_capacity(non_terminal_count);
+        let mut goto_rows = Vec::with_capacity(non_terminal_count);
+        for nt in 0..non_terminal_count {
+            let column: Vec<(usize, u32)> = (0..state_count)
+                .filter_map(|state| {
+                    table.goto(state, nt).map(|next| (state, next as u32))
+                })
+                .collect();
+
+            let default = most_common(column.iter().map(|&(_, next)| next))
+                .unwrap_or(NO_STATE);
+
+            default_goto.push(default);
+            goto_rows.push(
+                column
+                    .into_iter()
+                    .filter(|&(_, next)| next != default)
+                    .collect(),
+            );
+        }
+
+        CompressedTable {
+            state_count,
+            terminal_count,
+            non_terminal_count,
+
+            default_action,
+            action: Comb::new(&action_rows),
+            default_goto,
+            goto: Comb::new(&goto_rows),
+            conflicts: table.conflicts().to_vec(),
+        }
+    }
+
+    pub fn action(&self, state: usize, terminal: SymbolId) -> ActionEntry<'_> {
+        let packed = self.action
+            .get(state, terminal.index())
+            .unwrap_or(self.default_action[state]);
+        entry(packed, &self.conflicts)
+    }
+
+    /// Goto by non terminal index. Only meaningful for pairs that exist in
+    /// the dense table; any other pair yields the non terminal's default.
+    pub fn goto(&self, state: usize, non_terminal: usize) -> Option<usize> {
+        match self.goto.get(non_terminal, state).unwrap_or(
+            self.default_goto[non_terminal],
+        ) {
+            NO_STATE => None,
+            next => Some(next as usize),
+        }
+    }
+
+    /// The reduction used for every lookahead not listed for `state`.
+    pub fn default_reduction(&self, state: usize) -> Option<usize> {
+        match self.default_action[state] {
+            ERROR => None,
+            packed => Some((packed >> TAG_BITS) as usize),
+        }
+    }
+
+    pub fn report(&self) -> CompressionReport {
+        let dense_size = self.state_count * (self.terminal_count + self.non_terminal_count);
+        let compressed_size = self.default_action.len() + self.action.size() +
+            self.default_goto.len() + self.goto.size();
+
+        CompressionReport {
+            dense_size,
+            compressed_size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Grammar, Parser};
+    use super::super::test_util::*;
+
+    fn expr_parser() -> Parser {
+        let non_terminals = vec!["E", "T", "F"];
+        let prods = vec![
+            ("E", vec!["E", "+", "T"]),
+            ("E", vec!["E", "-", "T"]),
+            ("E", vec!["T"]),
+            ("T", vec!["T", "*", "F"]),
+            ("T", vec!["T", "/", "F"]),
+            ("T", vec!["F"]),
+            ("F", vec!["(", "E", ")"]),
+            ("F", vec!["num"]),
+        ];
+
+        Parser::new(Grammar::from_str("E", non_terminals, prods))
+    }
+
+    #[test]
+    fn lookup_test() {
+        let parser = expr_parser();
+        let table = parser.table();
+        let compressed = CompressedTable::new(table);
+        let symbols = parser.grammar().symbols();
+
+        for state in 0..table.state_count() {
+            for t in symbols.terminals() {
+                let expected = table.action(state, t);
+                let actual = compressed.action(state, t);
+                if expected == ActionEntry::Error {
+                    let default = compressed.default_reduction(state).map(Action::Reduce);
+                    assert_eq!(actual, default.map(ActionEntry::Action).unwrap_or(expected));
+                } else {
+                    assert_eq!(actual, expected, "state {} terminal {:?}", state, t);
+                }
+            }
+
+            for nt in 0..table.non_terminal_count() {
+                if let Some(next) = table.goto(state, nt) {
+                    assert_eq!(compressed.goto(state, nt), Some(next));
+                }
+            }
+        }
+
+        let report = compressed.report();
+        assert!(report.ratio() < 1.0, "{}", report);
+    }
+
+    #[test]
+    fn parse_compressed_test() {
+        let mut parser = expr_parser();
+        let report = parser.compress();
+        println!("{}", report);
+
+        for case in &["num EOF", "num + num * num EOF", "( num - num ) / num EOF"] {
+            let res = parser.parse(lex(case));
+            assert!(res.is_ok(), "case {:?}, res {}", case, res.err().unwrap());
+        }
+
+        for case in &["num + EOF", "( num EOF", "num num EOF"] {
+            assert!(parser.parse(lex(case)).is_err(), "case {:?}", case);
+        }
+    }
+}
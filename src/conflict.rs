@@ -0,0 +1,368 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+
+use super::{Action, Grammar, Item, Parser, StackEl, SymbolId, TokenLike};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ConflictKind {
+    ShiftReduce,
+    ReduceReduce,
+}
+
+impl fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConflictKind::ShiftReduce => write!(f, "shift/reduce"),
+            ConflictKind::ReduceReduce => write!(f, "reduce/reduce"),
+        }
+    }
+}
+
+/// A cell of the action table with more than one action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub state: usize,
+    pub lookahead: SymbolId,
+    pub kind: ConflictKind,
+    /// Every action of the cell, sorted.
+    pub actions: Vec<Action>,
+    /// The productions that could be reduced (0 for Accept).
+    pub productions: Vec<usize>,
+    /// The items of `state` behind the actions: items about to shift the
+    /// lookahead and complete items for it. Empty if the automaton was
+    /// dropped.
+    pub items: BTreeSet<Item>,
+}
+
+impl Conflict {
+    pub fn new(state: usize, lookahead: SymbolId, actions: &[Action], items: BTreeSet<Item>) -> Conflict {
+        let productions = actions
+            .iter()
+            .filter_map(|action| match *action {
+                Action::Accept => Some(0),
+                Action::Reduce(prod) => Some(prod),
+                Action::Shift(_) => None,
+            })
+            .collect();
+        let kind = if actions.iter().any(|action| matches!(*action, Action::Shift(_))) {
+            ConflictKind::ShiftReduce
+        } else {
+            ConflictKind::ReduceReduce
+        };
+
+        Conflict {
+            state,
+            lookahead,
+            kind,
+            actions: actions.to_vec(),
+            productions,
+            items,
+        }
+    }
+
+    /// The action kept when the conflict is expected: the shift if there is
+    /// one, otherwise the reduction of the earliest production.
+    pub fn default_resolution(&self) -> Action {
+        self.actions
+            .iter()
+            .cloned()
+            .find(|action| matches!(*action, Action::Shift(_)))
+            .unwrap_or(self.actions[0])
+    }
+
+    /// Keeps the items of a state that take part in a conflict on `lookahead`.
+    pub fn involved_items(items: BTreeSet<Item>, lookahead: SymbolId, g: &Grammar) -> BTreeSet<Item> {
+        items
+            .into_iter()
+            .filter(|item| if item.is_complete(g) {
+                item.lookahead == lookahead
+            } else {
+                item.stacktop(g) == Some(lookahead)
+            })
+            .collect()
+    }
+
+    pub fn to_string(&self, g: &Grammar) -> String {
+        let actions: Vec<String> = self.actions
+            .iter()
+            .map(|action| match *action {
+                Action::Accept => "accept".to_string(),
+                Action::Shift(state) => format!("shift {}", state),
+                Action::Reduce(prod) => format!("reduce {}", g.productions[prod]),
+            })
+            .collect();
+
+        let mut s = format!(
+            "{} conflict in state {} on {}: {}",
+            self.kind,
+            self.state,
+            g.symbols().symbol(self.lookahead),
+            actions.join(" | ")
+        );
+        for item in &self.items {
+            s.push_str("\n    ");
+            s.push_str(&item.to_string(g));
+        }
+        s
+    }
+}
+
+/// Chooses among the actions of a conflicting cell while parsing, for
+/// constructs that can only be told apart by context.
+///
+/// Installed with `Parser::set_conflict_resolver`; `parse` calls it whenever
+/// the current state has more than one action on the lookahead. Returning
+/// `None`, or an action that is not a candidate, fails the parse.
+pub trait ConflictResolver {
+    fn resolve(
+        &self,
+        state: usize,
+        token: &dyn TokenLike,
+        candidates: &[Action],
+        stack: &[StackEl],
+    ) -> Option<Action>;
+}
+
+impl<F> ConflictResolver for F
+where
+    F: Fn(usize, &dyn TokenLike, &[Action], &[StackEl]) -> Option<Action>,
+{
+    fn resolve(
+        &self,
+        state: usize,
+        token: &dyn TokenLike,
+        candidates: &[Action],
+        stack: &[StackEl],
+    ) -> Option<Action> {
+        self(state, token, candidates, stack)
+    }
+}
+
+/// Returned by `Parser::try_new` when the grammar has conflicts.
+#[derive(Debug, Clone)]
+pub struct ConflictError {
+    pub conflicts: Vec<Conflict>,
+    // Rendered with the grammar's symbol names, which the conflicts alone
+    // don't have, and counterexamples.
+    report: String,
+}
+
+impl ConflictError {
+    pub fn new(conflicts: Vec<Conflict>, parser: &Parser) -> ConflictError {
+        let report = conflicts
+            .iter()
+            .map(|conflict| {
+                let mut s = conflict.to_string(parser.grammar());
+                if let Some(counterexample) = parser.counterexample(conflict) {
+                    s.push('\n');
+                    s.push_str(&counterexample.to_string(parser.grammar()));
+                }
+                s
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        ConflictError { conflicts, report }
+    }
+}
+
+impl fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} conflicts\n{}", self.conflicts.len(), self.report)
+    }
+}
+
+impl Error for ConflictError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::ParseError;
+    use super::super::test_util::*;
+
+    fn reduce_reduce_grammar() -> Grammar {
+        Grammar::from_str(
+            "S",
+            vec!["S", "A", "B"],
+            vec![
+                ("S", vec!["A", "x"]),
+                ("S", vec!["B", "x"]),
+                ("A", vec!["a"]),
+                ("B", vec!["a"]),
+            ],
+        )
+    }
+
+    #[test]
+    fn shift_reduce_test() {
+        let parser = Parser::new(sum_grammar());
+        let plus = parser.grammar().symbols().terminal_id("+").unwrap();
+
+        let conflicts = parser.conflicts();
+        assert_eq!(conflicts.len(), 1);
+
+        let conflict = &conflicts[0];
+        assert_eq!(conflict.kind, ConflictKind::ShiftReduce);
+        assert_eq!(conflict.lookahead, plus);
+        assert_eq!(conflict.productions, vec![1]);
+        assert_eq!(
+            Item::set_to_string(&conflict.items, parser.grammar()),
+            "[NT(\"E\") -> E • + E, T(\"EOF\")] [NT(\"E\") -> E • + E, T(\"+\")] \
+             [NT(\"E\") -> E + E •, T(\"+\")]"
+        );
+        println!("{}", conflict.to_string(parser.grammar()));
+
+        let err = Parser::try_new(sum_grammar()).err().unwrap();
+        assert_eq!(err.conflicts, conflicts);
+    }
+
+    #[test]
+    fn reduce_reduce_test() {
+        let conflicts = Parser::new(reduce_reduce_grammar()).conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, ConflictKind::ReduceReduce);
+        assert_eq!(conflicts[0].productions, vec![3, 4]);
+        assert_eq!(conflicts[0].items.len(), 2);
+
+        let err = Parser::try_new(reduce_reduce_grammar()).err().unwrap();
+        assert!(err.to_string().starts_with("1 conflicts\nreduce/reduce conflict"), "{}", err);
+    }
+
+    #[test]
+    fn expected_conflicts_test() {
+        let mut g = dangling_else_grammar();
+        g.expect_conflicts(1, 0);
+        let parser = Parser::try_new(g).unwrap();
+
+        assert!(parser.is_lr1());
+        assert!(parser.conflicts().is_empty());
+        assert!(parser.table().conflicts().is_empty());
+        assert_eq!(parser.resolved_conflicts().len(), 1);
+        assert_eq!(
+            parser.warnings(),
+            vec!["shift/reduce conflict in state 13 on else resolved as shift".to_string()]
+        );
+
+        // The else goes with the innermost if.
+        let tree = parser.parse(dangling_else_tokens()).unwrap();
+        let root = tree.node(tree.root().unwrap()).unwrap();
+        assert_eq!(root.children.len(), 4);
+    }
+
+    #[test]
+    fn unexpected_conflicts_test() {
+        let mut g = dangling_else_grammar();
+        g.expect_conflicts(0, 0);
+        let err = Parser::try_new(g).err().unwrap();
+        assert_eq!(err.conflicts.len(), 1);
+
+        let mut g = dangling_else_grammar();
+        g.expect_conflicts(0, 0);
+        let warnings = Parser::new(g).warnings();
+        assert_eq!(
+            warnings.last().unwrap(),
+            "expected 0 shift/reduce and 0 reduce/reduce conflicts, found 1 and 0"
+        );
+
+        // Without expectations nothing is resolved.
+        let parser = Parser::new(dangling_else_grammar());
+        assert_eq!(parser.conflicts().len(), 1);
+        assert!(parser.warnings().is_empty());
+    }
+
+    #[test]
+    fn reduce_reduce_resolution_test() {
+        let mut g = reduce_reduce_grammar();
+        g.expect_conflicts(0, 1);
+        let parser = Parser::try_new(g).unwrap();
+        let x = parser.grammar().symbols().terminal_id("x").unwrap();
+
+        assert_eq!(parser.resolved_conflicts()[0].default_resolution(), Action::Reduce(3));
+        let state = parser.resolved_conflicts()[0].state;
+        assert_eq!(parser.table().actions(state, x), vec![Action::Reduce(3)]);
+    }
+
+    fn dangling_else_tokens() -> Box<dyn Iterator<Item = Box<dyn TokenLike>>> {
+        lex("if c then if c then x else x EOF")
+    }
+
+    #[test]
+    fn conflict_resolver_test() {
+        let mut parser = Parser::new(dangling_else_grammar());
+        assert!(parser.parse(dangling_else_tokens()).is_err());
+
+        // Attach the else to the outermost if: reduce the inner one first.
+        parser.set_conflict_resolver(|_: usize, token: &dyn TokenLike, candidates: &[Action], stack: &[StackEl]| {
+            assert_eq!(token.kind(), "else");
+            assert!(!stack.is_empty());
+            candidates.iter().cloned().find(|action| matches!(*action, Action::Reduce(_)))
+        });
+        let tree = parser.parse(dangling_else_tokens()).unwrap();
+        let root = tree.node(tree.root().unwrap()).unwrap();
+        assert_eq!(root.children.len(), 6);
+
+        parser.set_conflict_resolver(|_: usize, _: &dyn TokenLike, candidates: &[Action], _: &[StackEl]| {
+            candidates.iter().cloned().find(|action| matches!(*action, Action::Shift(_)))
+        });
+        let tree = parser.parse(dangling_else_tokens()).unwrap();
+        let root = tree.node(tree.root().unwrap()).unwrap();
+        assert_eq!(root.children.len(), 4);
+    }
+
+    #[test]
+    fn rejecting_resolver_test() {
+        let mut parser = Parser::new(dangling_else_grammar());
+        parser.set_conflict_resolver(|_: usize, _: &dyn TokenLike, _: &[Action], _: &[StackEl]| None);
+        let err = parser.parse(dangling_else_tokens()).err().unwrap().to_string();
+        assert!(err.starts_with("Conflict resolver rejected shift/reduce conflict"), "{}", err);
+
+        parser.set_conflict_resolver(|_: usize, _: &dyn TokenLike, _: &[Action], _: &[StackEl]| {
+            Some(Action::Accept)
+        });
+        match parser.parse(dangling_else_tokens()).err().unwrap() {
+            ParseError::Conflict { ref message, .. } => assert!(message.contains("not one of"), "{}", message),
+            err => panic!("Unexpected {:?}", err),
+        }
+    }
+
+    #[test]
+    fn set_actions_test() {
+        use super::super::Table;
+        use Action::*;
+
+        let mut table = Table::empty(2, 3, 0);
+        let (a, b) = (SymbolId(1), SymbolId(2));
+        table.add_action(0, a, Shift(1));
+        table.add_action(0, a, Reduce(1));
+        table.add_action(1, b, Shift(0));
+        table.add_action(1, b, Reduce(2));
+        assert_eq!(table.conflicts().len(), 2);
+
+        // A cell that still conflicts keeps its slot.
+        table.set_actions(0, a, &[Reduce(2), Reduce(1), Reduce(2)]);
+        assert_eq!(table.conflicts().len(), 2);
+        assert_eq!(table.actions(0, a), vec![Reduce(1), Reduce(2)]);
+
+        // A resolved one frees it, for the last conflict.
+        table.set_actions(0, a, &[Reduce(1)]);
+        assert_eq!(table.conflicts(), &[vec![Reduce(2), Shift(0)]]);
+        assert_eq!(table.actions(0, a), vec![Reduce(1)]);
+        assert_eq!(table.actions(1, b), vec![Reduce(2), Shift(0)]);
+
+        table.set_actions(1, b, &[]);
+        assert!(table.conflicts().is_empty());
+        assert!(!table.has_conflicts());
+    }
+
+    #[test]
+    fn no_conflicts_test() {
+        let g = Grammar::from_str(
+            "E",
+            vec!["E"],
+            vec![("E", vec!["E", "+", "num"]), ("E", vec!["num"])],
+        );
+
+        assert!(Parser::try_new(g).unwrap().conflicts().is_empty());
+    }
+}
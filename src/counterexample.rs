@@ -0,0 +1,610 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::{Action, Automaton, Conflict, Grammar, Item, SymbolId, SymbolTable};
+
+/// A derivation tree over grammar symbols, with a marker for the point where
+/// the parser has to choose between the actions of a conflict.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Derivation {
+    Symbol(SymbolId),
+    /// Where the conflict happens, right before the lookahead.
+    Dot,
+    /// A production and the derivations of its right hand side.
+    Node(usize, Vec<Derivation>),
+}
+
+impl Derivation {
+    /// The leaves of the derivation, `None` standing for the dot.
+    pub fn sentence(&self) -> Vec<Option<SymbolId>> {
+        let mut sentence = Vec::new();
+        self.push_leaves(&mut sentence);
+        sentence
+    }
+
+    fn push_leaves(&self, sentence: &mut Vec<Option<SymbolId>>) {
+        match *self {
+            Derivation::Symbol(s) => sentence.push(Some(s)),
+            Derivation::Dot => sentence.push(None),
+            Derivation::Node(_, ref children) => {
+                for child in children {
+                    child.push_leaves(sentence);
+                }
+            }
+        }
+    }
+
+    pub fn sentence_to_string(&self, g: &Grammar) -> String {
+        self.sentence()
+            .iter()
+            .map(|s| match *s {
+                Some(s) => g.symbols().symbol(s).as_str(),
+                None => "•",
+            })
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// Nested productions, e.g. `[E -> [E -> E + E] • + E]`. The fake goal
+    /// production at the root is left out.
+    pub fn to_string(&self, g: &Grammar) -> String {
+        match *self {
+            Derivation::Node(0, ref children) => Derivation::children_to_string(children, g),
+            _ => self.node_to_string(g),
+        }
+    }
+
+    fn node_to_string(&self, g: &Grammar) -> String {
+        match *self {
+            Derivation::Symbol(s) => g.symbols().symbol(s).as_str().to_string(),
+            Derivation::Dot => "•".to_string(),
+            Derivation::Node(prod, ref children) => {
+                let from = g.symbols().symbol(g.rules()[prod].from);
+                if children.is_empty() {
+                    format!("[{} -> ]", from.as_str())
+                } else {
+                    format!(
+                        "[{} -> {}]",
+                        from.as_str(),
+                        Derivation::children_to_string(children, g)
+                    )
+                }
+            }
+        }
+    }
+
+    fn children_to_string(children: &[Derivation], g: &Grammar) -> String {
+        children
+            .iter()
+            .map(|child| child.node_to_string(g))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+/// An explanation of a `Conflict`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    /// Shortest sequence of symbols leading from state 0 to the conflict.
+    pub prefix: Vec<SymbolId>,
+    /// A derivation for every action of the conflict, in the same order.
+    pub derivations: Vec<(Action, Derivation)>,
+    /// Whether two of the derivations yield the same sentence, which proves
+    /// the grammar ambiguous. Otherwise each derivation is only an example of
+    /// an input that reaches the conflict.
+    pub unifying: bool,
+}
+
+impl Counterexample {
+    /// Explains a conflict of the parser built from `g` (with its fake goal)
+    /// and `automaton`.
+    pub fn new(g: &Grammar, automaton: &Automaton, conflict: &Conflict) -> Counterexample {
+        let path = state_path(automaton, conflict.state);
+        let prefix: Vec<SymbolId> = path.iter().map(|&(_, x)| x).collect();
+        let builder = Builder::new(g, conflict.lookahead);
+
+        // Every action gets one candidate derivation per involved item; shift
+        // items come in one copy per lookahead, and any of them may be the one
+        // that unifies with a reduction.
+        let candidates: Vec<(Action, Vec<Derivation>)> = conflict
+            .actions
+            .iter()
+            .map(|&action| {
+                let derivations = conflict
+                    .items
+                    .iter()
+                    .filter(|item| match action {
+                        Action::Shift(_) => !item.is_complete(g),
+                        Action::Reduce(prod) => item.prod == prod && item.is_complete(g),
+                        Action::Accept => item.prod == 0 && item.is_complete(g),
+                    })
+                    .filter_map(|item| builder.derivation(&prefix, *item))
+                    .collect();
+                (action, derivations)
+            })
+            .collect();
+
+        let mut chosen: Vec<Option<Derivation>> = candidates
+            .iter()
+            .map(|(_, derivations)| derivations.first().cloned())
+            .collect();
+
+        let unifying = unify(&candidates);
+        if let Some((i, ref a, j, ref b)) = unifying {
+            chosen[i] = Some(a.clone());
+            chosen[j] = Some(b.clone());
+        }
+
+        Counterexample {
+            prefix,
+            derivations: candidates
+                .iter()
+                .zip(chosen)
+                .filter_map(|(&(action, _), derivation)| derivation.map(|d| (action, d)))
+                .collect(),
+            unifying: unifying.is_some(),
+        }
+    }
+
+    pub fn to_string(&self, g: &Grammar) -> String {
+        let prefix: Vec<&str> = self.prefix
+            .iter()
+            .map(|&s| g.symbols().symbol(s).as_str())
+            .collect();
+        let mut s = format!("Prefix: {}", prefix.join(" "));
+
+        if self.unifying {
+            if let Some((_, first)) = self.derivations.first() {
+                s.push_str(&format!("\nUnifying example: {}", first.sentence_to_string(g)));
+            }
+        }
+        for &(action, ref derivation) in &self.derivations {
+            let action = match action {
+                Action::Accept => "accept".to_string(),
+                Action::Shift(_) => "shift".to_string(),
+                Action::Reduce(prod) => format!("reduce {}", g.productions[prod]),
+            };
+            if !self.unifying {
+                s.push_str(&format!(
+                    "\nExample for {}: {}",
+                    action,
+                    derivation.sentence_to_string(g)
+                ));
+            }
+            s.push_str(&format!("\n    {}: {}", action, derivation.to_string(g)));
+        }
+        s
+    }
+}
+
+// Finds two derivations of different actions with the same sentence.
+fn unify(candidates: &[(Action, Vec<Derivation>)]) -> Option<(usize, Derivation, usize, Derivation)> {
+    for i in 0..candidates.len() {
+        for j in i + 1..candidates.len() {
+            for a in &candidates[i].1 {
+                for b in &candidates[j].1 {
+                    if a.sentence() == b.sentence() {
+                        return Some((i, a.clone(), j, b.clone()));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Shortest path of transitions from state 0 to `target`: the states left
+/// and the symbol of every transition.
+pub fn state_path(automaton: &Automaton, target: usize) -> Vec<(usize, SymbolId)> {
+    let mut parent: HashMap<usize, (usize, SymbolId)> = HashMap::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(0);
+
+    while let Some(state) = queue.pop_front() {
+        if state == target {
+            break;
+        }
+        for (&x, &next) in &automaton.states[state].transitions {
+            if next != 0 && !parent.contains_key(&next) {
+                parent.insert(next, (state, x));
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let mut path = Vec::new();
+    let mut state = target;
+    while let Some(&(prev, x)) = parent.get(&state) {
+        path.push((prev, x));
+        state = prev;
+    }
+    path.reverse();
+    path
+}
+
+struct Builder<'a> {
+    g: &'a Grammar,
+    lookahead: SymbolId,
+    // Cheapest (in nodes) derivation of LAMBDA and of a sentence starting with
+    // the lookahead, per non terminal index.
+    empty_cost: Vec<Option<usize>>,
+    lead_cost: Vec<Option<usize>>,
+}
+
+impl<'a> Builder<'a> {
+    fn new(g: &'a Grammar, lookahead: SymbolId) -> Builder<'a> {
+        let mut builder = Builder {
+            g,
+            lookahead,
+            empty_cost: vec![None; g.symbols().non_terminal_count()],
+            lead_cost: vec![None; g.symbols().non_terminal_count()],
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (prod, rule) in g.rules().iter().enumerate() {
+                let nt = g.symbols().non_terminal_index(rule.from);
+                if let Some(cost) = builder.empty_rule_cost(prod) {
+                    if builder.empty_cost[nt].map(|c| cost < c).unwrap_or(true) {
+                        builder.empty_cost[nt] = Some(cost);
+                        changed = true;
+                    }
+                }
+                if let Some((cost, _)) = builder.lead_rule_cost(prod) {
+                    if builder.lead_cost[nt].map(|c| cost < c).unwrap_or(true) {
+                        builder.lead_cost[nt] = Some(cost);
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        builder
+    }
+
+    fn cost_of(&self, costs: &[Option<usize>], s: SymbolId) -> Option<usize> {
+        if self.g.symbols().is_terminal(s) {
+            None
+        } else {
+            costs[self.g.symbols().non_terminal_index(s)]
+        }
+    }
+
+    fn empty_rule_cost(&self, prod: usize) -> Option<usize> {
+        self.g.rules()[prod]
+            .to
+            .iter()
+            .map(|&s| self.cost_of(&self.empty_cost, s))
+            .sum::<Option<usize>>()
+            .map(|cost| cost + 1)
+    }
+
+    // Cost of deriving a sentence that starts with the lookahead through
+    // `prod`, and the position of the symbol that yields it.
+    fn lead_rule_cost(&self, prod: usize) -> Option<(usize, usize)> {
+        let mut best: Option<(usize, usize)> = None;
+        let mut skipped = 1;
+        for (i, &s) in self.g.rules()[prod].to.iter().enumerate() {
+            let cost = if s == self.lookahead {
+                Some(skipped)
+            } else {
+                self.cost_of(&self.lead_cost, s).map(|c| c + skipped)
+            };
+            if let Some(cost) = cost {
+                if best.map(|(c, _)| cost < c).unwrap_or(true) {
+                    best = Some((cost, i));
+                }
+            }
+
+            match self.cost_of(&self.empty_cost, s) {
+                Some(c) => skipped += c,
+                None => break,
+            }
+        }
+        best
+    }
+
+    fn empty(&self, s: SymbolId) -> Derivation {
+        let nt = self.g.symbols().non_terminal_index(s);
+        let prod = self.g
+            .prods_of(s)
+            .iter()
+            .cloned()
+            .find(|&p| self.empty_rule_cost(p) == self.empty_cost[nt])
+            .expect("No empty derivation");
+        let children = self.g.rules()[prod].to.iter().map(|&s| self.empty(s)).collect();
+        Derivation::Node(prod, children)
+    }
+
+    // A derivation of `s` whose first terminal is the lookahead.
+    fn lead(&self, s: SymbolId) -> Derivation {
+        if s == self.lookahead {
+            return Derivation::Symbol(s);
+        }
+
+        let nt = self.g.symbols().non_terminal_index(s);
+        let (prod, at) = self.g
+            .prods_of(s)
+            .iter()
+            .filter_map(|&p| self.lead_rule_cost(p).map(|(cost, at)| (p, cost, at)))
+            .find(|&(_, cost, _)| Some(cost) == self.lead_cost[nt])
+            .map(|(p, _, at)| (p, at))
+            .expect("No derivation starting with the lookahead");
+
+        let to = &self.g.rules()[prod].to;
+        let mut children: Vec<Derivation> = to[..at].iter().map(|&s| self.empty(s)).collect();
+        children.push(self.lead(to[at]));
+        children.extend(to[at + 1..].iter().map(|&s| Derivation::Symbol(s)));
+        Derivation::Node(prod, children)
+    }
+
+    // Shortest path of items from the start item to `target`, following the
+    // `prefix` transitions, as the stack of items it goes through.
+    fn item_path(&self, prefix: &[SymbolId], target: Item) -> Option<Vec<Item>> {
+        let start = (0, Item::new(0, 0, SymbolTable::eof()));
+        let mut parent: HashMap<(usize, Item), (usize, Item)> = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+
+        let mut found = false;
+        while let Some((k, item)) = queue.pop_front() {
+            if (k, item) == (prefix.len(), target) {
+                found = true;
+                break;
+            }
+
+            let rule = &self.g.rules()[item.prod];
+            let next = match rule.to.get(item.stacktop) {
+                Some(&next) => next,
+                None => continue,
+            };
+
+            let mut successors = Vec::new();
+            if prefix.get(k) == Some(&next) {
+                successors.push((k + 1, Item::new(item.prod, item.stacktop + 1, item.lookahead)));
+            }
+            if !self.g.symbols().is_terminal(next) {
+                let (mut first, nullable) = self.g.first_of_ids(&rule.to[item.stacktop + 1..]);
+                if nullable {
+                    first.insert(item.lookahead.index());
+                }
+                for &p in self.g.prods_of(next) {
+                    for la in first.iter() {
+                        successors.push((k, Item::new(p, 0, SymbolId(la as u32))));
+                    }
+                }
+            }
+
+            for successor in successors {
+                if successor != start && !parent.contains_key(&successor) {
+                    parent.insert(successor, (k, item));
+                    queue.push_back(successor);
+                }
+            }
+        }
+
+        if !found {
+            return None;
+        }
+
+        let mut nodes = vec![(prefix.len(), target)];
+        while let Some(&node) = parent.get(nodes.last().unwrap()) {
+            nodes.push(node);
+        }
+        nodes.reverse();
+
+        // Transitions move the dot of the innermost item, production steps
+        // open a new one.
+        let mut stack: Vec<Item> = Vec::new();
+        for (i, &(k, item)) in nodes.iter().enumerate() {
+            if i > 0 && k > nodes[i - 1].0 {
+                stack.pop();
+            }
+            stack.push(item);
+        }
+        Some(stack)
+    }
+
+    fn derivation(&self, prefix: &[SymbolId], target: Item) -> Option<Derivation> {
+        let stack = self.item_path(prefix, target)?;
+        let leaves = |item: &Item, range: ::std::ops::Range<usize>| -> Vec<Derivation> {
+            self.g.rules()[item.prod].to[range]
+                .iter()
+                .map(|&s| Derivation::Symbol(s))
+                .collect()
+        };
+
+        let mut items = stack.iter().rev();
+        let inner = items.next().unwrap();
+        let len = self.g.rules()[inner.prod].to.len();
+
+        // The innermost item either shifts the lookahead (the dot goes inside
+        // it) or is reduced, and the lookahead has to follow it.
+        let mut pending = inner.stacktop == len;
+        let mut node = if pending {
+            Derivation::Node(inner.prod, leaves(inner, 0..len))
+        } else {
+            let mut children = leaves(inner, 0..inner.stacktop);
+            children.push(Derivation::Dot);
+            children.extend(leaves(inner, inner.stacktop..len));
+            Derivation::Node(inner.prod, children)
+        };
+
+        for item in items {
+            let to = &self.g.rules()[item.prod].to;
+            let mut children = leaves(item, 0..item.stacktop);
+            children.push(node);
+
+            let rest = item.stacktop + 1..to.len();
+            if pending {
+                children.push(Derivation::Dot);
+                for &s in &to[rest.clone()] {
+                    if !pending || (self.g.symbols().is_terminal(s) && s != self.lookahead) {
+                        children.push(Derivation::Symbol(s));
+                        pending = false;
+                    } else if s == self.lookahead || self.cost_of(&self.lead_cost, s).is_some() {
+                        children.push(self.lead(s));
+                        pending = false;
+                    } else {
+                        children.push(self.empty(s));
+                    }
+                }
+                if pending {
+                    // The dot goes after whatever this item derives instead.
+                    children.retain(|child| *child != Derivation::Dot);
+                }
+            } else {
+                children.extend(leaves(item, rest));
+            }
+
+            node = Derivation::Node(item.prod, children);
+        }
+
+        if pending {
+            // Only EOF can follow the whole input.
+            if let Derivation::Node(prod, mut children) = node {
+                children.push(Derivation::Dot);
+                children.push(Derivation::Symbol(SymbolTable::eof()));
+                node = Derivation::Node(prod, children);
+            }
+        }
+
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Parser, LAMBDA};
+
+    fn explain(parser: &Parser) -> Vec<Counterexample> {
+        parser
+            .conflicts()
+            .iter()
+            .map(|conflict| {
+                let counterexample = parser.counterexample(conflict).unwrap();
+                println!("{}", conflict.to_string(parser.grammar()));
+                println!("{}", counterexample.to_string(parser.grammar()));
+                counterexample
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ambiguous_sum_test() {
+        let g = Grammar::from_str(
+            "E",
+            vec!["E"],
+            vec![("E", vec!["E", "+", "E"]), ("E", vec!["num"])],
+        );
+        let parser = Parser::new(g);
+        let g = parser.grammar();
+
+        let counterexamples = explain(&parser);
+        assert_eq!(counterexamples.len(), 1);
+
+        let counterexample = &counterexamples[0];
+        assert!(counterexample.unifying);
+        let prefix: Vec<&str> = counterexample.prefix.iter().map(|&s| g.symbols().symbol(s).as_str()).collect();
+        assert_eq!(prefix, vec!["E", "+", "E"]);
+
+        let derivations: Vec<(String, String)> = counterexample
+            .derivations
+            .iter()
+            .map(|(_, d)| (d.sentence_to_string(g), d.to_string(g)))
+            .collect();
+        assert_eq!(
+            derivations,
+            vec![
+                ("E + E • + E".to_string(), "[E -> [E -> E + E] • + E]".to_string()),
+                ("E + E • + E".to_string(), "[E -> E + [E -> E • + E]]".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn dangling_else_test() {
+        let g = Grammar::from_str(
+            "S",
+            vec!["S"],
+            vec![
+                ("S", vec!["if", "c", "then", "S"]),
+                ("S", vec!["if", "c", "then", "S", "else", "S"]),
+                ("S", vec!["x"]),
+            ],
+        );
+        let parser = Parser::new(g);
+        let g = parser.grammar();
+
+        let counterexamples = explain(&parser);
+        assert_eq!(counterexamples.len(), 1);
+        assert!(counterexamples[0].unifying);
+        assert_eq!(
+            counterexamples[0].derivations[0].1.sentence_to_string(g),
+            "if c then if c then S • else S"
+        );
+    }
+
+    #[test]
+    fn lookahead_expansion_test() {
+        // The reduction of `A -> a` needs `B` expanded to show the `b` that
+        // follows it.
+        let g = Grammar::from_str(
+            "S",
+            vec!["S", "A", "B", "C"],
+            vec![
+                ("S", vec!["A", "B"]),
+                ("S", vec!["a", "b"]),
+                ("A", vec!["a"]),
+                ("B", vec!["C", "b"]),
+                ("C", vec![LAMBDA]),
+            ],
+        );
+        let parser = Parser::new(g);
+        let g = parser.grammar();
+
+        let counterexamples = explain(&parser);
+        assert_eq!(counterexamples.len(), 1);
+        assert!(counterexamples[0].unifying);
+        let derivations: Vec<String> = counterexamples[0]
+            .derivations
+            .iter()
+            .map(|(_, d)| d.to_string(g))
+            .collect();
+        assert_eq!(
+            derivations,
+            vec![
+                "[S -> [A -> a] • [B -> [C -> ] b]]".to_string(),
+                "[S -> a • b]".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn non_unifying_test() {
+        // LR(2): the conflict on `b` is only decided by the next token.
+        let g = Grammar::from_str(
+            "S",
+            vec!["S", "A", "B"],
+            vec![
+                ("S", vec!["A", "b", "c"]),
+                ("S", vec!["B", "b", "d"]),
+                ("A", vec!["a"]),
+                ("B", vec!["a"]),
+            ],
+        );
+        let parser = Parser::new(g);
+        let g = parser.grammar();
+
+        let counterexamples = explain(&parser);
+        assert_eq!(counterexamples.len(), 1);
+        assert!(!counterexamples[0].unifying);
+        let sentences: Vec<String> = counterexamples[0]
+            .derivations
+            .iter()
+            .map(|(_, d)| d.sentence_to_string(g))
+            .collect();
+        assert_eq!(sentences, vec!["a • b c", "a • b d"]);
+    }
+}
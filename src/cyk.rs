@@ -0,0 +1,469 @@
+use std::collections::HashMap;
+
+use super::{Grammar, NodeId, ParseError, SymbolId, TokenLike, Tree, EOF};
+
+// CNF symbols are the grammar's symbol indices, followed by the non
+// terminals introduced by the conversion.
+type CnfSymbol = usize;
+
+// How a CNF rule maps back to the original productions. Every CNF symbol
+// stands for a tuple of sequences of original subtrees: one sequence for the
+// grammar's symbols, one per remaining symbol for the symbols introduced to
+// split long rules.
+#[derive(Debug, Clone)]
+enum Fragment {
+    /// The `j`th sequence of the `i`th symbol of the rule.
+    Hole(usize, usize),
+    /// A node of an original production.
+    Node(usize, Vec<Fragment>),
+    /// The empty derivation of an original non terminal.
+    Empty(SymbolId),
+}
+
+#[derive(Debug, Clone)]
+struct CnfRule {
+    from: CnfSymbol,
+    /// A terminal, or two non terminals.
+    to: Vec<CnfSymbol>,
+    /// One fragment per sequence of `from`.
+    fragments: Vec<Fragment>,
+}
+
+// An original subtree, before it is copied into a `Tree`.
+#[derive(Debug, Clone)]
+enum Piece {
+    Token(usize),
+    Node(usize, Vec<Piece>),
+    Empty(SymbolId),
+}
+
+/// A CYK parser, run on the grammar converted to Chomsky normal form.
+///
+/// Parses any grammar in cubic time and maps the derivation back to the
+/// original productions, so it makes a slow but independent oracle for the
+/// LR parsers. Unlike `Parser`, an input made only of EOF is parsed too
+/// (into the goal's empty derivation, if it has one).
+pub struct CykParser {
+    grammar: Grammar,
+    goal: SymbolId,
+    rules: Vec<CnfRule>,
+    // A production deriving LAMBDA for every nullable non terminal.
+    empty_prods: HashMap<SymbolId, usize>,
+}
+
+impl CykParser {
+    pub fn new(g: Grammar) -> CykParser {
+        let grammar = g.with_fake_goal();
+        let goal = grammar.rules()[0].to[0];
+        let empty_prods = empty_prods(&grammar);
+        let mut next_symbol = grammar.symbols().len();
+
+        // Rules without the empty positions, in every combination.
+        let mut rules = Vec::new();
+        for (prod, rule) in grammar.rules().iter().enumerate().skip(1) {
+            let holes = (0..rule.to.len()).map(|i| Fragment::Hole(i, 0)).collect();
+            let fragment = Fragment::Node(prod, holes);
+            let nullable: Vec<usize> = (0..rule.to.len())
+                .filter(|&i| empty_prods.contains_key(&rule.to[i]))
+                .collect();
+
+            for mask in 0..1usize << nullable.len() {
+                let dropped: Vec<usize> = nullable
+                    .iter()
+                    .enumerate()
+                    .filter(|&(bit, _)| mask & 1 << bit != 0)
+                    .map(|(_, &i)| i)
+                    .collect();
+                if dropped.len() == rule.to.len() {
+                    continue;
+                }
+
+                let kept: Vec<usize> = (0..rule.to.len()).filter(|i| !dropped.contains(i)).collect();
+                let fragment = fragment.map_holes(&|i, j| match kept.iter().position(|&k| k == i) {
+                    Some(pos) => Fragment::Hole(pos, j),
+                    None => Fragment::Empty(rule.to[i]),
+                });
+                rules.push(CnfRule {
+                    from: rule.from.index(),
+                    to: kept.iter().map(|&i| rule.to[i].index()).collect(),
+                    fragments: vec![fragment],
+                });
+            }
+        }
+
+        let rules = remove_units(&grammar, rules);
+
+        // Terminals of long rules get a non terminal of their own.
+        let mut terminal_symbols: HashMap<CnfSymbol, CnfSymbol> = HashMap::new();
+        let mut cnf = Vec::new();
+        for mut rule in rules {
+            if rule.to.len() > 1 {
+                for x in rule.to.iter_mut() {
+                    if grammar.symbols().is_terminal(SymbolId(*x as u32)) {
+                        let terminal = *x;
+                        *x = *terminal_symbols.entry(terminal).or_insert_with(|| {
+                            cnf.push(CnfRule {
+                                from: next_symbol,
+                                to: vec![terminal],
+                                fragments: vec![Fragment::Hole(0, 0)],
+                            });
+                            next_symbol += 1;
+                            next_symbol - 1
+                        });
+                    }
+                }
+            }
+
+            // Long rules are split into pairs: the symbol introduced for
+            // the tail carries one sequence per symbol of the tail.
+            while rule.to.len() > 2 {
+                let to = rule.to.split_off(1);
+                let tail = CnfRule {
+                    from: next_symbol,
+                    fragments: (0..to.len()).map(|i| Fragment::Hole(i, 0)).collect(),
+                    to,
+                };
+                next_symbol += 1;
+
+                rule.to.push(tail.from);
+                rule.fragments = rule.fragments
+                    .iter()
+                    .map(|fragment| fragment.map_holes(&|i, j| match i {
+                        0 => Fragment::Hole(0, j),
+                        i => Fragment::Hole(1, i - 1),
+                    }))
+                    .collect();
+                cnf.push(rule);
+                rule = tail;
+            }
+            cnf.push(rule);
+        }
+
+        CykParser {
+            grammar,
+            goal,
+            rules: cnf,
+            empty_prods,
+        }
+    }
+
+    pub fn grammar(&self) -> &Grammar {
+        &self.grammar
+    }
+
+    /// The number of rules of the grammar in Chomsky normal form.
+    pub fn cnf_size(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Whether the tokens, up to EOF, are a sentence of the grammar.
+    pub fn accepts<I>(&self, tokens: I) -> bool
+    where
+        I: Iterator<Item = Box<dyn TokenLike>>,
+    {
+        self.parse(tokens).is_ok()
+    }
+
+    /// Parses the tokens, up to EOF, into a tree over the original
+    /// productions. Ambiguous inputs get one of their trees.
+    ///
+    /// CYK only tells whole sentences apart, so errors come with no expected
+    /// terminals, and an input that is not a sentence fails at its EOF.
+    pub fn parse<I>(&self, tokens: I) -> Result<Tree, ParseError>
+    where
+        I: Iterator<Item = Box<dyn TokenLike>>,
+    {
+        let unexpected = |index: usize, word: &dyn TokenLike| ParseError::UnexpectedToken {
+            index,
+            kind: word.kind().clone(),
+            lexeme: word.lexeme().clone(),
+            state: 0,
+            expected: Vec::new(),
+        };
+
+        let mut words: Vec<Box<dyn TokenLike>> = Vec::new();
+        let mut terminals = Vec::new();
+        let mut eof = None;
+        for word in tokens {
+            let terminal = match self.grammar.symbols().terminal_id(word.kind()) {
+                Some(terminal) => terminal,
+                None => return Err(unexpected(words.len(), &*word)),
+            };
+            if word.kind() == EOF {
+                eof = Some(word);
+                break;
+            }
+            words.push(word);
+            terminals.push(terminal.index());
+        }
+        let eof = eof.ok_or(ParseError::UnexpectedEnd {
+            state: 0,
+            expected: Vec::new(),
+        })?;
+
+        let n = terminals.len();
+        let piece = if n == 0 {
+            if !self.empty_prods.contains_key(&self.goal) {
+                return Err(unexpected(n, &*eof));
+            }
+            Piece::Empty(self.goal)
+        } else {
+            let table = self.table(&terminals);
+            if !table[n][0].contains_key(&self.goal.index()) {
+                return Err(unexpected(n, &*eof));
+            }
+            let mut sequences = self.pieces(&table, self.goal.index(), 0, n);
+            sequences.remove(0).remove(0)
+        };
+
+        let mut tree = Tree::new();
+        let root = self.build(&mut tree, &piece, &words);
+        tree.set_root(root);
+        Ok(tree)
+    }
+
+    // For every span length and start, the symbols deriving the span, with
+    // the rule and the split of one derivation.
+    fn table(&self, terminals: &[CnfSymbol]) -> Vec<Vec<HashMap<CnfSymbol, (usize, usize)>>> {
+        let n = terminals.len();
+        let mut table = vec![vec![HashMap::new(); n]; n + 1];
+
+        for (i, &terminal) in terminals.iter().enumerate() {
+            for (r, rule) in self.rules.iter().enumerate() {
+                if rule.to == [terminal] {
+                    table[1][i].entry(rule.from).or_insert((r, 0));
+                }
+            }
+        }
+
+        for len in 2..n + 1 {
+            for start in 0..n + 1 - len {
+                for split in 1..len {
+                    for (r, rule) in self.rules.iter().enumerate() {
+                        if rule.to.len() == 2 &&
+                            table[split][start].contains_key(&rule.to[0]) &&
+                            table[len - split][start + split].contains_key(&rule.to[1])
+                        {
+                            table[len][start].entry(rule.from).or_insert((r, split));
+                        }
+                    }
+                }
+            }
+        }
+
+        table
+    }
+
+    fn pieces(
+        &self,
+        table: &[Vec<HashMap<CnfSymbol, (usize, usize)>>],
+        symbol: CnfSymbol,
+        start: usize,
+        len: usize,
+    ) -> Vec<Vec<Piece>> {
+        let (r, split) = table[len][start][&symbol];
+        let rule = &self.rules[r];
+        let children = if rule.to.len() == 1 {
+            vec![vec![vec![Piece::Token(start)]]]
+        } else {
+            vec![
+                self.pieces(table, rule.to[0], start, split),
+                self.pieces(table, rule.to[1], start + split, len - split),
+            ]
+        };
+
+        rule.fragments.iter().map(|fragment| fragment.eval(&children)).collect()
+    }
+
+    fn build(&self, tree: &mut Tree, piece: &Piece, words: &[Box<dyn TokenLike>]) -> NodeId {
+        let (prod, children) = match *piece {
+            Piece::Token(i) => {
+                let token = (words[i].kind().clone(), words[i].lexeme().clone());
+                return tree.new_node(Box::new(token) as Box<dyn TokenLike>);
+            }
+            Piece::Node(prod, ref children) => (prod, children.clone()),
+            Piece::Empty(symbol) => {
+                let prod = self.empty_prods[&symbol];
+                let children = self.grammar.rules()[prod].to.iter().map(|&x| Piece::Empty(x)).collect();
+                (prod, children)
+            }
+        };
+
+        let children: Vec<NodeId> = children.iter().map(|child| self.build(tree, child, words)).collect();
+        let rule = &self.grammar.rules()[prod];
+        let parent = tree.new_node(self.grammar.symbols().symbol(rule.from).clone());
+        for child in children {
+            tree.append(parent, child);
+        }
+        parent
+    }
+}
+
+impl Fragment {
+    fn map_holes(&self, f: &dyn Fn(usize, usize) -> Fragment) -> Fragment {
+        match *self {
+            Fragment::Hole(i, j) => f(i, j),
+            Fragment::Node(prod, ref children) => {
+                Fragment::Node(prod, children.iter().map(|child| child.map_holes(f)).collect())
+            }
+            Fragment::Empty(symbol) => Fragment::Empty(symbol),
+        }
+    }
+
+    fn eval(&self, children: &[Vec<Vec<Piece>>]) -> Vec<Piece> {
+        match *self {
+            Fragment::Hole(i, j) => children[i][j].clone(),
+            Fragment::Node(prod, ref fragments) => {
+                let pieces = fragments.iter().flat_map(|fragment| fragment.eval(children)).collect();
+                vec![Piece::Node(prod, pieces)]
+            }
+            Fragment::Empty(symbol) => vec![Piece::Empty(symbol)],
+        }
+    }
+}
+
+// Picks, for every nullable non terminal, a production that derives LAMBDA
+// through non terminals picked before it.
+fn empty_prods(g: &Grammar) -> HashMap<SymbolId, usize> {
+    let mut empty_prods = HashMap::new();
+    loop {
+        let mut changed = false;
+        for (prod, rule) in g.rules().iter().enumerate().skip(1) {
+            if !empty_prods.contains_key(&rule.from) &&
+                rule.to.iter().all(|x| empty_prods.contains_key(x))
+            {
+                empty_prods.insert(rule.from, prod);
+                changed = true;
+            }
+        }
+        if !changed {
+            return empty_prods;
+        }
+    }
+}
+
+// Replaces unit rules `A -> B` by copies of the rules of `B`, nesting B's
+// fragment inside the chain of units.
+fn remove_units(g: &Grammar, rules: Vec<CnfRule>) -> Vec<CnfRule> {
+    let is_unit = |rule: &CnfRule| {
+        rule.to.len() == 1 && !g.symbols().is_terminal(SymbolId(rule.to[0] as u32))
+    };
+
+    let mut result = Vec::new();
+    for a in g.symbols().non_terminals() {
+        // The symbols A reaches through units, with the fragment wrapping
+        // the derivation of each (first path found).
+        let mut chains: Vec<(CnfSymbol, Fragment)> = vec![(a.index(), Fragment::Hole(0, 0))];
+        let mut i = 0;
+        while i < chains.len() {
+            let (b, chain) = chains[i].clone();
+            for rule in rules.iter().filter(|rule| rule.from == b) {
+                let fragment = chain.map_holes(&|_, _| rule.fragments[0].clone());
+                if !is_unit(rule) {
+                    result.push(CnfRule {
+                        from: a.index(),
+                        to: rule.to.clone(),
+                        fragments: vec![fragment],
+                    });
+                } else if chains.iter().all(|&(c, _)| c != rule.to[0]) {
+                    chains.push((rule.to[0], fragment));
+                }
+            }
+            i += 1;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{LAMBDA, Parser};
+    use super::super::test_util::*;
+
+    // Every sentence over `alphabet` up to `len` symbols, followed by EOF.
+    fn sentences(alphabet: &[&str], len: usize) -> Vec<String> {
+        let mut all = vec![String::new()];
+        let mut last = vec![String::new()];
+        for _ in 0..len {
+            last = last.iter()
+                .flat_map(|prefix| alphabet.iter().map(move |s| format!("{} {}", prefix, s)))
+                .collect();
+            all.extend(last.iter().cloned());
+        }
+        all.into_iter().map(|s| format!("{} EOF", s)).collect()
+    }
+
+    #[test]
+    fn oracle_test() {
+        let cyk = CykParser::new(balanced_grammar());
+        let parser = Parser::new(balanced_grammar());
+
+        for sentence in sentences(&["(", ")"], 8) {
+            let expected = parser.parse(lex(&sentence));
+            let tree = cyk.parse(lex(&sentence));
+            assert_eq!(tree.is_ok(), expected.is_ok(), "{}", sentence);
+            if sentence != " EOF" {
+                if let (Ok(tree), Ok(expected)) = (tree, expected) {
+                    assert_eq!(tree.to_string(), expected.to_string(), "{}", sentence);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn units_test() {
+        // Unit chains, a long rule with terminals and an empty goal.
+        let g = || Grammar::from_str(
+            "E",
+            vec!["E", "T", "F", "Opt"],
+            vec![
+                ("E", vec!["E", "+", "T"]),
+                ("E", vec!["T"]),
+                ("T", vec!["T", "*", "F"]),
+                ("T", vec!["F"]),
+                ("F", vec!["(", "E", ")", "Opt"]),
+                ("F", vec!["num"]),
+                ("Opt", vec!["!"]),
+                ("Opt", vec![LAMBDA]),
+            ],
+        );
+        let cyk = CykParser::new(g());
+        let parser = Parser::new(g());
+        assert!(parser.is_lr1());
+        assert!(cyk.cnf_size() > 8);
+
+        // `Parser` takes an empty input for an empty tree, whatever the goal.
+        for sentence in sentences(&["num", "+", "*", "(", ")", "!"], 4).iter().skip(1) {
+            let expected = parser.parse(lex(sentence));
+            let tree = cyk.parse(lex(sentence));
+            assert_eq!(tree.is_ok(), expected.is_ok(), "{}", sentence);
+            if let (Ok(tree), Ok(expected)) = (tree, expected) {
+                assert_eq!(tree.to_string(), expected.to_string(), "{}", sentence);
+            }
+        }
+
+        assert!(cyk.accepts(lex("( num + num ) ! * num EOF")));
+        assert!(!cyk.accepts(lex("( num + num ) ! * num")));
+    }
+
+    #[test]
+    fn empty_input_test() {
+        let cyk = CykParser::new(balanced_grammar());
+        let tree = cyk.parse(lex("EOF")).unwrap();
+        assert_eq!(tree.to_string(), "|-- NT(\"S\")\n");
+
+        let ambiguous = CykParser::new(sum_grammar());
+        assert!(ambiguous.accepts(lex("num + num + num EOF")));
+        assert!(!ambiguous.accepts(lex("EOF")));
+        assert!(!ambiguous.accepts(lex("num + EOF")));
+
+        // Rejected inputs fail at their EOF.
+        match ambiguous.parse(lex("num + EOF")).err().unwrap() {
+            ParseError::UnexpectedToken { index, ref kind, .. } => assert_eq!((index, kind.as_str()), (2, EOF)),
+            err => panic!("Unexpected {:?}", err),
+        }
+        assert!(matches!(ambiguous.parse(lex("num")), Err(ParseError::UnexpectedEnd { .. })));
+        assert_eq!(ambiguous.parse(lex("num x EOF")).err().unwrap().to_string(), "unexpected `x`");
+    }
+}
@@ -0,0 +1,349 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{Forest, ForestNode, ForestNodeId, Grammar, PackedNode, ParseError, SymbolId, SymbolNode,
+            SymbolTable, TokenLike, Tree, EOF};
+use super::error::terminal_names;
+
+// An Earley item: production, number of symbols recognized and the token
+// where the production started.
+type EarleyItem = (usize, usize, usize);
+
+/// An Earley parser: accepts any grammar, ambiguous, left or right
+/// recursive and with empty productions included, at a cubic worst case
+/// cost. Handy while a grammar is not LR(1) yet.
+pub struct EarleyParser {
+    grammar: Grammar,
+    nullable: Vec<bool>,
+}
+
+// The item sets of a parse, one per token position.
+struct Chart<'a> {
+    grammar: &'a Grammar,
+    sets: Vec<HashSet<EarleyItem>>,
+    terminals: Vec<SymbolId>,
+}
+
+impl EarleyParser {
+    pub fn new(g: Grammar) -> EarleyParser {
+        let grammar = g.with_fake_goal();
+        let nullable = (0..grammar.symbols().len())
+            .map(|i| grammar.first_of_ids(&[SymbolId(i as u32)]).1)
+            .collect();
+
+        EarleyParser { grammar, nullable }
+    }
+
+    pub fn grammar(&self) -> &Grammar {
+        &self.grammar
+    }
+
+    /// Parses the tokens, up to EOF, into a tree. Fails if the input is
+    /// ambiguous; see `parse_forest`.
+    pub fn parse<I>(&self, tokens: I) -> Result<Tree, ParseError>
+    where
+        I: Iterator<Item = Box<dyn TokenLike>>,
+    {
+        let forest = self.parse_forest(tokens)?;
+        if forest.root().is_none() {
+            return Ok(Tree::new());
+        }
+
+        match forest.count() {
+            1 => Ok(forest.trees().remove(0)),
+            count => Err(ParseError::Ambiguous(count)),
+        }
+    }
+
+    /// Parses the tokens, up to EOF, into a forest sharing every parse.
+    pub fn parse_forest<I>(&self, tokens: I) -> Result<Forest, ParseError>
+    where
+        I: Iterator<Item = Box<dyn TokenLike>>,
+    {
+        let mut words: Vec<Box<dyn TokenLike>> = Vec::new();
+        // Tokens of an unknown kind have no terminal, and stop the parse
+        // when it reaches them.
+        let mut terminals = Vec::new();
+        for word in tokens {
+            let eof = word.kind() == EOF;
+            let terminal = self.grammar.symbols().terminal_id(word.kind());
+            words.push(word);
+            if eof {
+                break;
+            }
+            terminals.push(terminal);
+        }
+        if words.is_empty() || terminals.is_empty() {
+            return Ok(Forest::default());
+        }
+
+        let chart = self.recognize(&terminals, &words)?;
+        let n = chart.terminals.len();
+        if words.len() == n {
+            return Err(ParseError::UnexpectedEnd {
+                state: 0,
+                expected: chart.expected(n),
+            });
+        }
+        if !chart.sets[n].contains(&(0, 1, 0)) {
+            return Err(chart.unexpected(n, &*words[n]));
+        }
+        let goal = self.grammar.rules()[0].to[0];
+
+        let mut forest = Forest::new(words);
+        let root = chart.symbol_node(&mut forest, goal, 0, n, &mut HashMap::new());
+        forest.set_root(root);
+        Ok(forest)
+    }
+
+    // Fills the item sets up to the last terminal, failing at the first
+    // token nothing can be read past.
+    fn recognize(
+        &self,
+        terminals: &[Option<SymbolId>],
+        words: &[Box<dyn TokenLike>],
+    ) -> Result<Chart<'_>, ParseError> {
+        let rules = self.grammar.rules();
+        let n = terminals.len();
+        let mut sets: Vec<HashSet<EarleyItem>> = vec![HashSet::new(); n + 1];
+        let mut pending: Vec<EarleyItem> = vec![(0, 0, 0)];
+        sets[0].insert((0, 0, 0));
+
+        for k in 0..n + 1 {
+            while let Some((prod, dot, origin)) = pending.pop() {
+                let mut add = |item: EarleyItem, sets: &mut Vec<HashSet<EarleyItem>>| {
+                    if sets[k].insert(item) {
+                        pending.push(item);
+                    }
+                };
+
+                match rules[prod].to.get(dot) {
+                    Some(&next) if self.grammar.symbols().is_terminal(next) => {
+                        if k < n && terminals[k] == Some(next) {
+                            sets[k + 1].insert((prod, dot + 1, origin));
+                        }
+                    }
+                    Some(&next) => {
+                        for &q in self.grammar.prods_of(next) {
+                            add((q, 0, k), &mut sets);
+                        }
+                        // Empty derivations complete right away, also for
+                        // items waiting on `next` that are added later.
+                        if self.nullable[next.index()] {
+                            add((prod, dot + 1, origin), &mut sets);
+                        }
+                    }
+                    None => {
+                        let from = rules[prod].from;
+                        let waiting: Vec<EarleyItem> = sets[origin]
+                            .iter()
+                            .filter(|&&(p, d, _)| rules[p].to.get(d) == Some(&from))
+                            .cloned()
+                            .collect();
+                        for (p, d, o) in waiting {
+                            add((p, d + 1, o), &mut sets);
+                        }
+                    }
+                }
+            }
+
+            if k < n {
+                if sets[k + 1].is_empty() {
+                    let chart = Chart {
+                        grammar: &self.grammar,
+                        sets,
+                        terminals: Vec::new(),
+                    };
+                    return Err(chart.unexpected(k, &*words[k]));
+                }
+                pending = sets[k + 1].iter().cloned().collect();
+            }
+        }
+
+        Ok(Chart {
+            grammar: &self.grammar,
+            sets,
+            terminals: terminals.iter().map(|t| t.expect("Unexpected unknown terminal")).collect(),
+        })
+    }
+}
+
+impl<'a> Chart<'a> {
+    // The names of the terminals the items of set `k` wait for, EOF
+    // included once the goal is complete.
+    fn expected(&self, k: usize) -> Vec<String> {
+        let symbols = self.grammar.symbols();
+        let terminals = self.sets[k].iter().filter_map(|&(prod, dot, origin)| {
+            match self.grammar.rules()[prod].to.get(dot) {
+                Some(&next) if symbols.is_terminal(next) => Some(next),
+                None if (prod, dot, origin) == (0, 1, 0) => Some(SymbolTable::eof()),
+                _ => None,
+            }
+        });
+        terminal_names(symbols, terminals)
+    }
+
+    fn unexpected(&self, k: usize, word: &dyn TokenLike) -> ParseError {
+        ParseError::UnexpectedToken {
+            index: k,
+            kind: word.kind().clone(),
+            lexeme: word.lexeme().clone(),
+            state: 0,
+            expected: self.expected(k),
+        }
+    }
+
+    // Whether `symbol` derives the tokens from `start` to `end`.
+    fn derives(&self, symbol: SymbolId, start: usize, end: usize) -> bool {
+        if self.grammar.symbols().is_terminal(symbol) {
+            end == start + 1 && self.terminals[start] == symbol
+        } else {
+            self.grammar.prods_of(symbol).iter().any(|&q| {
+                self.sets[end].contains(&(q, self.grammar.rules()[q].to.len(), start))
+            })
+        }
+    }
+
+    // The forest node of `symbol` over the tokens from `start` to `end`,
+    // which must derive them.
+    fn symbol_node(
+        &self,
+        forest: &mut Forest,
+        symbol: SymbolId,
+        start: usize,
+        end: usize,
+        ids: &mut HashMap<(SymbolId, usize, usize), ForestNodeId>,
+    ) -> ForestNodeId {
+        if self.grammar.symbols().is_terminal(symbol) {
+            return start;
+        }
+        if let Some(&id) = ids.get(&(symbol, start, end)) {
+            return id;
+        }
+
+        // Registered before the children, which may lead back here.
+        let id = forest.add_node(ForestNode::Symbol(SymbolNode {
+            symbol: self.grammar.symbols().symbol(symbol).clone(),
+            start,
+            end,
+            packed: Vec::new(),
+        }));
+        ids.insert((symbol, start, end), id);
+
+        for &prod in self.grammar.prods_of(symbol) {
+            let len = self.grammar.rules()[prod].to.len();
+            if !self.sets[end].contains(&(prod, len, start)) {
+                continue;
+            }
+
+            for spans in self.splits(prod, len, start, end) {
+                let to = &self.grammar.rules()[prod].to;
+                let children = to.iter()
+                    .zip(spans)
+                    .map(|(&child, (from, until))| self.symbol_node(forest, child, from, until, ids))
+                    .collect();
+                forest.add_packed(id, PackedNode { prod, children });
+            }
+        }
+        id
+    }
+
+    // Every way of splitting the tokens from `start` to `end` among the
+    // first `dot` symbols of `prod`, as one span per symbol.
+    fn splits(&self, prod: usize, dot: usize, start: usize, end: usize) -> Vec<Vec<(usize, usize)>> {
+        if dot == 0 {
+            return if start == end { vec![Vec::new()] } else { Vec::new() };
+        }
+
+        let symbol = self.grammar.rules()[prod].to[dot - 1];
+        let mut result = Vec::new();
+        for mid in start..end + 1 {
+            if !self.sets[mid].contains(&(prod, dot - 1, start)) || !self.derives(symbol, mid, end) {
+                continue;
+            }
+            for mut spans in self.splits(prod, dot - 1, start, mid) {
+                spans.push((mid, end));
+                result.push(spans);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{LAMBDA, Parser};
+    use super::super::test_util::*;
+
+    #[test]
+    fn same_tree_test() {
+        let earley = EarleyParser::new(list_grammar());
+        let lr1 = Parser::new(list_grammar());
+
+        for case in &["( ) EOF", "( ( ) ) ( ) EOF", "( ( ( ) ) ) ( ( ) ) ( ) EOF"] {
+            let expected = lr1.parse(lex(case)).unwrap();
+            let tree = earley.parse(lex(case)).unwrap();
+            assert_eq!(tree.to_string(), expected.to_string());
+        }
+
+        assert!(earley.parse(lex("EOF")).unwrap().root().is_none());
+        // The same errors too, but for the state.
+        for case in &["( ( ) EOF", "( ) )", "( )", "( x ) EOF", ") EOF"] {
+            let expected = lr1.parse(lex(case)).err().unwrap();
+            let err = earley.parse(lex(case)).err().unwrap();
+            assert_eq!(err.to_string(), expected.to_string(), "{}", case);
+            assert_eq!(err.expected(), expected.expected(), "{}", case);
+        }
+    }
+
+    #[test]
+    fn ambiguous_test() {
+        let earley = EarleyParser::new(sum_grammar());
+
+        let forest = earley.parse_forest(lex("num + num + num + num EOF")).unwrap();
+        assert_eq!(forest.count(), 5);
+        let err = earley.parse(lex("num + num + num EOF")).err().unwrap();
+        assert_eq!(err, ParseError::Ambiguous(2));
+    }
+
+    #[test]
+    fn recursion_test() {
+        let earley = EarleyParser::new(Grammar::from_str(
+            "S",
+            vec!["S", "L", "R"],
+            vec![
+                ("S", vec!["L", "R"]),
+                ("L", vec!["L", "a"]),
+                ("L", vec!["a"]),
+                ("R", vec!["b", "R"]),
+                ("R", vec!["b"]),
+            ],
+        ));
+
+        let tree = earley.parse(lex("a a a b b EOF")).unwrap();
+        assert_eq!(tree.to_string().matches("NT(\"L\")").count(), 3);
+        assert_eq!(tree.to_string().matches("NT(\"R\")").count(), 2);
+    }
+
+    #[test]
+    fn lambda_test() {
+        // Every symbol is nullable, and A is ambiguous on empty spans.
+        let earley = EarleyParser::new(Grammar::from_str(
+            "S",
+            vec!["S", "A", "B"],
+            vec![
+                ("S", vec!["A", "B", "A"]),
+                ("A", vec!["a"]),
+                ("A", vec![LAMBDA]),
+                ("B", vec!["B", "b"]),
+                ("B", vec![LAMBDA]),
+            ],
+        ));
+
+        for &(case, count) in &[("a b b a EOF", 1), ("b EOF", 1), ("a EOF", 2), ("a a EOF", 1)] {
+            let forest = earley.parse_forest(lex(case)).unwrap();
+            assert_eq!(forest.count(), count, "{}", case);
+        }
+        assert!(earley.parse_forest(lex("a a a EOF")).is_err());
+    }
+}
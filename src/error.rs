@@ -0,0 +1,130 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+
+use super::{SymbolId, SymbolTable};
+
+/// Why a parse failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The token at `index` has no action in `state`, or is not a terminal
+    /// of the grammar at all.
+    ///
+    /// `state` is an LR state: the lowest of the stack tops for `GlrParser`,
+    /// and 0 for `EarleyParser` and `CykParser`, which have none.
+    UnexpectedToken {
+        index: usize,
+        kind: String,
+        lexeme: String,
+        state: usize,
+        /// The terminals that would have been accepted instead.
+        expected: Vec<String>,
+    },
+    /// The tokens ran out before EOF.
+    UnexpectedEnd { state: usize, expected: Vec<String> },
+    /// The token at `index` reached a conflicting cell that was not
+    /// resolved: there is no conflict resolver, or it failed.
+    Conflict {
+        index: usize,
+        state: usize,
+        message: String,
+    },
+    /// The input has this many parses, where a single tree was asked for.
+    Ambiguous(usize),
+    /// A broken table or stack, not a problem with the input.
+    Internal(String),
+}
+
+// The names of `terminals` in id order, as in `expected`.
+pub(crate) fn terminal_names<I>(symbols: &SymbolTable, terminals: I) -> Vec<String>
+where
+    I: IntoIterator<Item = SymbolId>,
+{
+    let terminals: BTreeSet<SymbolId> = terminals.into_iter().collect();
+    terminals.into_iter().map(|t| symbols.symbol(t).as_str().to_string()).collect()
+}
+
+impl ParseError {
+    pub fn expected(&self) -> &[String] {
+        match *self {
+            ParseError::UnexpectedToken { ref expected, .. } |
+            ParseError::UnexpectedEnd { ref expected, .. } => expected,
+            _ => &[],
+        }
+    }
+}
+
+// `a`, `b` or `c`
+fn write_expected(f: &mut fmt::Formatter, expected: &[String]) -> fmt::Result {
+    for (i, terminal) in expected.iter().enumerate() {
+        if i > 0 {
+            write!(f, "{}", if i + 1 == expected.len() { " or " } else { ", " })?;
+        }
+        write!(f, "`{}`", terminal)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::UnexpectedToken {
+                ref kind,
+                ref lexeme,
+                ref expected,
+                ..
+            } => {
+                let found = if lexeme.is_empty() { kind } else { lexeme };
+                if expected.is_empty() {
+                    write!(f, "unexpected `{}`", found)
+                } else {
+                    write!(f, "expected ")?;
+                    write_expected(f, expected)?;
+                    write!(f, ", found `{}`", found)
+                }
+            }
+            ParseError::UnexpectedEnd { ref expected, .. } => {
+                if expected.is_empty() {
+                    write!(f, "unexpected end of input")
+                } else {
+                    write!(f, "expected ")?;
+                    write_expected(f, expected)?;
+                    write!(f, ", found end of input")
+                }
+            }
+            ParseError::Conflict { ref message, .. } => write!(f, "{}", message),
+            ParseError::Ambiguous(count) => write!(f, "ambiguous input: {} parses", count),
+            ParseError::Internal(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_test() {
+        let expected = |terminals: &[&str]| terminals.iter().map(|t| t.to_string()).collect();
+        let unexpected = |lexeme: &str, terminals: &[&str]| ParseError::UnexpectedToken {
+            index: 3,
+            kind: "id".to_string(),
+            lexeme: lexeme.to_string(),
+            state: 4,
+            expected: expected(terminals),
+        };
+
+        assert_eq!(unexpected("x", &[")", "("]).to_string(), "expected `)` or `(`, found `x`");
+        assert_eq!(unexpected("", &[";"]).to_string(), "expected `;`, found `id`");
+        assert_eq!(unexpected("x", &[]).to_string(), "unexpected `x`");
+        let end = ParseError::UnexpectedEnd {
+            state: 2,
+            expected: expected(&["+", "*", ";"]),
+        };
+        assert_eq!(end.to_string(), "expected `+`, `*` or `;`, found end of input");
+        assert_eq!(end.expected().len(), 3);
+        assert_eq!(ParseError::Ambiguous(2).to_string(), "ambiguous input: 2 parses");
+    }
+}
@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+
+use super::{NodeId, Symbol, TokenLike, Tree};
+
+pub type ForestNodeId = usize;
+
+/// A node of a `Forest`.
+pub enum ForestNode {
+    /// The token at this index of the input.
+    Token(usize),
+    Symbol(SymbolNode),
+}
+
+/// A non terminal over a span of tokens, with every way of deriving it.
+pub struct SymbolNode {
+    pub symbol: Symbol,
+    /// First token of the span.
+    pub start: usize,
+    /// One past the last token of the span.
+    pub end: usize,
+    /// More than one packed node means the span is ambiguous.
+    pub packed: Vec<PackedNode>,
+}
+
+/// One derivation of a symbol node: a production and its children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedNode {
+    pub prod: usize,
+    pub children: Vec<ForestNodeId>,
+}
+
+/// A shared packed parse forest: every parse of an input in a single graph.
+///
+/// Subtrees common to several parses are stored once, and ambiguous spans
+/// keep their alternatives side by side as packed nodes, so the forest stays
+/// polynomial in the input even when the number of trees is not.
+/// A non terminal that derives itself makes the forest cyclic; derivations
+/// that go around a cycle are ignored when counting and extracting trees.
+#[derive(Default)]
+pub struct Forest {
+    nodes: Vec<ForestNode>,
+    root: Option<ForestNodeId>,
+    tokens: Vec<Box<dyn TokenLike>>,
+}
+
+impl Forest {
+    /// A forest over `tokens`, with one node per token: the node of the
+    /// token `i` is `i`.
+    pub fn new(tokens: Vec<Box<dyn TokenLike>>) -> Forest {
+        Forest {
+            nodes: (0..tokens.len()).map(ForestNode::Token).collect(),
+            root: None,
+            tokens,
+        }
+    }
+
+    pub fn add_node(&mut self, node: ForestNode) -> ForestNodeId {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// Adds a derivation to a symbol node.
+    pub fn add_packed(&mut self, id: ForestNodeId, packed: PackedNode) {
+        if let ForestNode::Symbol(ref mut node) = self.nodes[id] {
+            if !node.packed.contains(&packed) {
+                node.packed.push(packed);
+            }
+        } else {
+            panic!("Forest.add_packed(): {} is not a symbol node", id);
+        }
+    }
+
+    pub fn set_root(&mut self, root: ForestNodeId) {
+        self.root = Some(root);
+    }
+
+    pub fn root(&self) -> Option<ForestNodeId> {
+        self.root
+    }
+
+    pub fn node(&self, id: ForestNodeId) -> Option<&ForestNode> {
+        self.nodes.get(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn token(&self, i: usize) -> Option<&dyn TokenLike> {
+        self.tokens.get(i).map(|token| &**token)
+    }
+
+    /// Whether some span has more than one derivation.
+    pub fn is_ambiguous(&self) -> bool {
+        self.nodes.iter().any(|node| match *node {
+            ForestNode::Symbol(ref node) => node.packed.len() > 1,
+            ForestNode::Token(_) => false,
+        })
+    }
+
+    /// The number of trees in the forest, without building them.
+    pub fn count(&self) -> usize {
+        match self.root {
+            Some(root) => self.count_node(root, &mut HashMap::new(), &mut Vec::new()).0,
+            None => 0,
+        }
+    }
+
+    // Counts the trees below `id`, and whether a cycle was cut on the way
+    // (in which case the count depends on the path and is not memoized).
+    fn count_node(
+        &self,
+        id: ForestNodeId,
+        memo: &mut HashMap<ForestNodeId, usize>,
+        visiting: &mut Vec<ForestNodeId>,
+    ) -> (usize, bool) {
+        let node = match self.nodes[id] {
+            ForestNode::Token(_) => return (1, false),
+            ForestNode::Symbol(ref node) => node,
+        };
+        if let Some(&count) = memo.get(&id) {
+            return (count, false);
+        }
+        if visiting.contains(&id) {
+            return (0, true);
+        }
+
+        visiting.push(id);
+        let mut total = 0usize;
+        let mut cut = false;
+        for packed in &node.packed {
+            let mut product = 1usize;
+            for &child in &packed.children {
+                let (count, child_cut) = self.count_node(child, memo, visiting);
+                product = product.saturating_mul(count);
+                cut |= child_cut;
+            }
+            total = total.saturating_add(product);
+        }
+        visiting.pop();
+
+        if !cut {
+            memo.insert(id, total);
+        }
+        (total, cut)
+    }
+
+    /// Every tree in the forest. There may be exponentially many; see
+    /// `count` and `tree_with`.
+    pub fn trees(&self) -> Vec<Tree> {
+        let root = match self.root {
+            Some(root) => root,
+            None => return Vec::new(),
+        };
+
+        self.derivations(root, &mut Vec::new())
+            .iter()
+            .map(|derivation| {
+                let mut tree = Tree::new();
+                let id = self.build(&mut tree, derivation);
+                tree.set_root(id);
+                tree
+            })
+            .collect()
+    }
+
+    /// Extracts a single tree, keeping at every ambiguous node the packed
+    /// nodes accepted by `filter`.
+    ///
+    /// Fails if the filter rejects every derivation of a node, or keeps more
+    /// than one.
+    pub fn tree_with<F>(&self, filter: F) -> Result<Tree, String>
+    where
+        F: Fn(&Forest, ForestNodeId, &PackedNode) -> bool,
+    {
+        let mut tree = Tree::new();
+        if let Some(root) = self.root {
+            let id = self.build_with(&mut tree, root, &filter, &mut Vec::new())?;
+            tree.set_root(id);
+        }
+        Ok(tree)
+    }
+
+    fn build_with<F>(
+        &self,
+        tree: &mut Tree,
+        id: ForestNodeId,
+        filter: &F,
+        visiting: &mut Vec<ForestNodeId>,
+    ) -> Result<NodeId, String>
+    where
+        F: Fn(&Forest, ForestNodeId, &PackedNode) -> bool,
+    {
+        let node = match self.nodes[id] {
+            ForestNode::Token(i) => return Ok(self.build(tree, &Derivation::Token(i))),
+            ForestNode::Symbol(ref node) => node,
+        };
+        if visiting.contains(&id) {
+            return Err(format!("Cyclic derivation of {} at {}..{}", node.symbol, node.start, node.end));
+        }
+
+        let kept: Vec<&PackedNode> = if node.packed.len() == 1 {
+            node.packed.iter().collect()
+        } else {
+            node.packed.iter().filter(|packed| filter(self, id, packed)).collect()
+        };
+        if kept.len() != 1 {
+            return Err(format!(
+                "{} derivations of {} at {}..{} left by the filter",
+                kept.len(),
+                node.symbol,
+                node.start,
+                node.end
+            ));
+        }
+
+        visiting.push(id);
+        let children = kept[0].children
+            .iter()
+            .map(|&child| self.build_with(tree, child, filter, visiting))
+            .collect::<Result<Vec<NodeId>, String>>()?;
+        visiting.pop();
+
+        let parent = tree.new_node(node.symbol.clone());
+        for child in children {
+            tree.append(parent, child);
+        }
+        Ok(parent)
+    }
+
+    fn derivations(&self, id: ForestNodeId, visiting: &mut Vec<ForestNodeId>) -> Vec<Derivation> {
+        let node = match self.nodes[id] {
+            ForestNode::Token(i) => return vec![Derivation::Token(i)],
+            ForestNode::Symbol(ref node) => node,
+        };
+        if visiting.contains(&id) {
+            return Vec::new();
+        }
+
+        visiting.push(id);
+        let mut result = Vec::new();
+        for packed in &node.packed {
+            let mut partial: Vec<Vec<Derivation>> = vec![Vec::new()];
+            for &child in &packed.children {
+                let options = self.derivations(child, visiting);
+                partial = partial
+                    .iter()
+                    .flat_map(|prefix| {
+                        options.iter().map(move |option| {
+                            let mut prefix = prefix.clone();
+                            prefix.push(option.clone());
+                            prefix
+                        })
+                    })
+                    .collect();
+            }
+            result.extend(partial.into_iter().map(|children| Derivation::Node(id, children)));
+        }
+        visiting.pop();
+
+        result
+    }
+
+    fn build(&self, tree: &mut Tree, derivation: &Derivation) -> NodeId {
+        match *derivation {
+            Derivation::Token(i) => {
+                let token = &self.tokens[i];
+                let token = (token.kind().clone(), token.lexeme().clone());
+                tree.new_node(Box::new(token) as Box<dyn TokenLike>)
+            }
+            Derivation::Node(id, ref children) => {
+                let children: Vec<NodeId> = children
+                    .iter()
+                    .map(|child| self.build(tree, child))
+                    .collect();
+                let symbol = match self.nodes[id] {
+                    ForestNode::Symbol(ref node) => node.symbol.clone(),
+                    ForestNode::Token(_) => unreachable!(),
+                };
+                let parent = tree.new_node(symbol);
+                for child in children {
+                    tree.append(parent, child);
+                }
+                parent
+            }
+        }
+    }
+}
+
+// One tree of the forest, before it is copied into a `Tree`.
+#[derive(Clone)]
+enum Derivation {
+    Token(usize),
+    Node(ForestNodeId, Vec<Derivation>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Grammar, GlrParser};
+    use super::super::test_util::*;
+
+    #[test]
+    fn sharing_test() {
+        let parser = GlrParser::new(sum_grammar());
+        let forest = parser.parse_forest(lex("num + num + num + num + num + num EOF")).unwrap();
+
+        assert!(forest.is_ambiguous());
+        // Catalan(5) trees, but only one symbol node per span.
+        assert_eq!(forest.count(), 42);
+        assert_eq!(forest.trees().len(), 42);
+        let symbol_nodes = (0..forest.len())
+            .filter(|&id| matches!(*forest.node(id).unwrap(), ForestNode::Symbol(_)))
+            .count();
+        assert_eq!(symbol_nodes, 6 * 7 / 2);
+
+        let root = match *forest.node(forest.root().unwrap()).unwrap() {
+            ForestNode::Symbol(ref node) => node,
+            ForestNode::Token(_) => panic!("Unexpected token root"),
+        };
+        assert_eq!((root.start, root.end, root.packed.len()), (0, 11, 5));
+    }
+
+    #[test]
+    fn tree_with_test() {
+        let parser = GlrParser::new(sum_grammar());
+        let forest = parser.parse_forest(lex("num + num + num EOF")).unwrap();
+
+        // Left associativity: the right operand of + is never a sum.
+        let left = forest.tree_with(|forest, _, packed| {
+            match *forest.node(packed.children[2]).unwrap() {
+                ForestNode::Symbol(ref node) => node.packed.iter().all(|p| p.prod == 2),
+                ForestNode::Token(_) => true,
+            }
+        }).unwrap();
+        let root = left.node(left.root().unwrap()).unwrap();
+        assert_eq!(left.node(root.children[0]).unwrap().children.len(), 3);
+
+        let err = forest.tree_with(|_, _, _| true).err().unwrap();
+        assert!(err.starts_with("2 derivations of NT(\"E\") at 0..5"), "{}", err);
+        assert!(forest.tree_with(|_, _, _| false).is_err());
+
+        // Unambiguous forests need no filter.
+        let forest = parser.parse_forest(lex("num + num EOF")).unwrap();
+        assert!(!forest.is_ambiguous());
+        assert_eq!(forest.count(), 1);
+        assert!(forest.tree_with(|_, _, _| false).is_ok());
+    }
+
+    #[test]
+    fn cyclic_test() {
+        // S -> S makes every S node derive itself.
+        let parser = GlrParser::new(Grammar::from_str(
+            "S",
+            vec!["S"],
+            vec![("S", vec!["S"]), ("S", vec!["a"])],
+        ));
+        let forest = parser.parse_forest(lex("a EOF")).unwrap();
+
+        assert_eq!(forest.count(), 1);
+        assert_eq!(forest.trees().len(), 1);
+        assert!(forest.tree_with(|_, _, packed| packed.prod == 2).is_ok());
+        assert!(forest.tree_with(|_, _, packed| packed.prod == 1).is_err());
+    }
+}
@@ -0,0 +1,426 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+
+use super::{Action, Automaton, Grammar, SymbolId, Table, TokenLike, Tree, EOF, Forest, ForestNode,
+            ForestNodeId, PackedNode, ParseError, SymbolNode};
+use super::error::terminal_names;
+
+/// The outcome of a GLR parse.
+pub enum GlrResult {
+    /// The input has a single parse.
+    Unique(Tree),
+    /// The input is ambiguous: every parse, in no particular order.
+    Ambiguous(Vec<Tree>),
+}
+
+impl GlrResult {
+    pub fn is_ambiguous(&self) -> bool {
+        matches!(*self, GlrResult::Ambiguous(_))
+    }
+
+    pub fn trees(self) -> Vec<Tree> {
+        match self {
+            GlrResult::Unique(tree) => vec![tree],
+            GlrResult::Ambiguous(trees) => trees,
+        }
+    }
+}
+
+// The semantic value of a GSS edge: a token or a non terminal.
+#[derive(Clone)]
+enum Value {
+    Token(usize),
+    Symbol(Rc<Packed>),
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (&Value::Token(a), &Value::Token(b)) => a == b,
+            (Value::Symbol(a), Value::Symbol(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+// Every derivation of a non terminal over the same span of tokens, as
+// (production, children) pairs. Shared by every edge that spans it, so
+// alternatives found after an edge was used still show up in its value.
+struct Packed {
+    symbol: SymbolId,
+    start: usize,
+    end: usize,
+    alternatives: RefCell<Vec<(usize, Vec<Value>)>>,
+}
+
+// A node of the graph-structured stack. Edges point towards the bottom.
+struct GssNode {
+    state: usize,
+    level: usize,
+    edges: Vec<(usize, Value)>,
+}
+
+// Edges down the stack, as (node, edge index) pairs.
+type Path = Vec<(usize, usize)>;
+
+/// A generalized LR parser: runs on LR(1) tables with conflicts by forking
+/// the stack on every conflicting cell, sharing the common parts in a graph
+/// structured stack.
+///
+/// Shift/reduce conflicts resolved by precedence stay resolved; everything
+/// else is explored.
+pub struct GlrParser {
+    grammar: Grammar,
+    table: Table,
+}
+
+impl GlrParser {
+    pub fn new(g: Grammar) -> GlrParser {
+        let grammar = g.with_fake_goal();
+        let table = Table::new(&Automaton::new(&grammar), &grammar);
+
+        GlrParser { grammar, table }
+    }
+
+    pub fn grammar(&self) -> &Grammar {
+        &self.grammar
+    }
+
+    pub fn table(&self) -> &Table {
+        &self.table
+    }
+
+    /// Parses the tokens, up to EOF, into every possible tree.
+    pub fn parse<I>(&self, tokens: I) -> Result<GlrResult, ParseError>
+    where
+        I: Iterator<Item = Box<dyn TokenLike>>,
+    {
+        let forest = self.parse_forest(tokens)?;
+        if forest.root().is_none() {
+            return Ok(GlrResult::Unique(Tree::new()));
+        }
+
+        let mut trees = forest.trees();
+        if trees.len() == 1 {
+            Ok(GlrResult::Unique(trees.remove(0)))
+        } else {
+            Ok(GlrResult::Ambiguous(trees))
+        }
+    }
+
+    /// Parses the tokens, up to EOF, into a forest sharing every parse.
+    pub fn parse_forest<I>(&self, mut tokens: I) -> Result<Forest, ParseError>
+    where
+        I: Iterator<Item = Box<dyn TokenLike>>,
+    {
+        let mut words: Vec<Box<dyn TokenLike>> = Vec::new();
+        match tokens.next() {
+            Some(ref word) if word.kind() == EOF => return Ok(Forest::default()),
+            Some(word) => words.push(word),
+            None => return Ok(Forest::default()),
+        }
+
+        let mut nodes = vec![GssNode { state: 0, level: 0, edges: Vec::new() }];
+        // GSS nodes of the current level, by state.
+        let mut level: BTreeMap<usize, usize> = BTreeMap::new();
+        level.insert(0, 0);
+
+        loop {
+            let i = words.len() - 1;
+            // The stack tops before the reductions, which only happen on
+            // terminals with an action there.
+            let tops: Vec<usize> = level.values().map(|&v| nodes[v].state).collect();
+            let terminal = match self.grammar.symbols().terminal_id(words[i].kind()) {
+                Some(terminal) => terminal,
+                None => return Err(self.unexpected(i, &*words[i], &tops)),
+            };
+
+            self.reduce_all(&mut nodes, &mut level, i, terminal)?;
+
+            let mut next: BTreeMap<usize, usize> = BTreeMap::new();
+            for &v in level.values() {
+                for action in self.table.actions(nodes[v].state, terminal) {
+                    match action {
+                        Action::Shift(state) => {
+                            let w = *next.entry(state).or_insert_with(|| {
+                                nodes.push(GssNode { state, level: i + 1, edges: Vec::new() });
+                                nodes.len() - 1
+                            });
+                            nodes[w].edges.push((v, Value::Token(i)));
+                        }
+                        Action::Accept => {
+                            // The accepting node sits on the goal, which
+                            // sits on the bottom of the stack.
+                            let goal = nodes[v].edges
+                                .iter()
+                                .find(|&&(bottom, _)| bottom == 0)
+                                .map(|edge| edge.1.clone())
+                                .expect("Accept without a goal");
+                            return Ok(self.forest(&goal, words));
+                        }
+                        Action::Reduce(_) => {}
+                    }
+                }
+            }
+
+            if next.is_empty() {
+                return Err(self.unexpected(i, &*words[i], &tops));
+            }
+            level = next;
+
+            let word = match tokens.next() {
+                Some(word) => word,
+                None => {
+                    let tops: Vec<usize> = level.keys().cloned().collect();
+                    return Err(ParseError::UnexpectedEnd {
+                        state: tops[0],
+                        expected: self.expected(&tops),
+                    });
+                }
+            };
+            words.push(word);
+        }
+    }
+
+    // The names of the terminals with an action in any of `states`.
+    fn expected(&self, states: &[usize]) -> Vec<String> {
+        let symbols = self.grammar.symbols();
+        let terminals = symbols.terminals().into_iter().filter(|&t| {
+            states.iter().any(|&state| !self.table.actions(state, t).is_empty())
+        });
+        terminal_names(symbols, terminals)
+    }
+
+    fn unexpected(&self, index: usize, word: &dyn TokenLike, states: &[usize]) -> ParseError {
+        ParseError::UnexpectedToken {
+            index,
+            kind: word.kind().clone(),
+            lexeme: word.lexeme().clone(),
+            state: states.iter().cloned().min().unwrap_or(0),
+            expected: self.expected(states),
+        }
+    }
+
+    // Does every reduction of the current level on `terminal`, until no new
+    // edge appears. Reductions are done once per path, so paths through
+    // edges added later are still reduced.
+    fn reduce_all(
+        &self,
+        nodes: &mut Vec<GssNode>,
+        level: &mut BTreeMap<usize, usize>,
+        i: usize,
+        terminal: SymbolId,
+    ) -> Result<(), ParseError> {
+        let mut packed: HashMap<(SymbolId, usize), Rc<Packed>> = HashMap::new();
+        let mut done: HashSet<(usize, usize, Path)> = HashSet::new();
+
+        loop {
+            let mut changed = false;
+            let current: Vec<usize> = level.values().cloned().collect();
+
+            for v in current {
+                for action in self.table.actions(nodes[v].state, terminal) {
+                    let prod = match action {
+                        Action::Reduce(prod) => prod,
+                        _ => continue,
+                    };
+                    let rule = &self.grammar.rules()[prod];
+
+                    for path in paths(nodes, v, rule.to.len()) {
+                        if !done.insert((v, prod, path.clone())) {
+                            continue;
+                        }
+                        changed = true;
+
+                        let bottom = path.last()
+                            .map(|&(node, edge)| nodes[node].edges[edge].0)
+                            .unwrap_or(v);
+                        let children: Vec<Value> = path.iter()
+                            .rev()
+                            .map(|&(node, edge)| nodes[node].edges[edge].1.clone())
+                            .collect();
+
+                        let index = self.grammar.symbols().non_terminal_index(rule.from);
+                        let state = self.table.goto(nodes[bottom].state, index).ok_or_else(|| {
+                            ParseError::Internal(format!(
+                                "Missing goto from state {} on {}",
+                                nodes[bottom].state,
+                                self.grammar.symbols().symbol(rule.from)
+                            ))
+                        })?;
+
+                        let value = packed
+                            .entry((rule.from, nodes[bottom].level))
+                            .or_insert_with(|| Rc::new(Packed {
+                                symbol: rule.from,
+                                start: nodes[bottom].level,
+                                end: i,
+                                alternatives: RefCell::new(Vec::new()),
+                            }))
+                            .clone();
+                        {
+                            let mut alternatives = value.alternatives.borrow_mut();
+                            if !alternatives.iter().any(|alt| alt.0 == prod && alt.1 == children) {
+                                alternatives.push((prod, children));
+                            }
+                        }
+
+                        let w = *level.entry(state).or_insert_with(|| {
+                            nodes.push(GssNode { state, level: i, edges: Vec::new() });
+                            nodes.len() - 1
+                        });
+                        if !nodes[w].edges.iter().any(|edge| edge.0 == bottom) {
+                            nodes[w].edges.push((bottom, Value::Symbol(value)));
+                        }
+                    }
+                }
+            }
+
+            if !changed {
+                return Ok(());
+            }
+        }
+    }
+
+    // Copies the packed values reachable from the goal into a forest.
+    fn forest(&self, goal: &Value, words: Vec<Box<dyn TokenLike>>) -> Forest {
+        let mut forest = Forest::new(words);
+        let mut ids = HashMap::new();
+        let root = self.forest_node(&mut forest, goal, &mut ids);
+        forest.set_root(root);
+        forest
+    }
+
+    fn forest_node(
+        &self,
+        forest: &mut Forest,
+        value: &Value,
+        ids: &mut HashMap<*const Packed, ForestNodeId>,
+    ) -> ForestNodeId {
+        let packed = match *value {
+            Value::Token(i) => return i,
+            Value::Symbol(ref packed) => packed,
+        };
+        if let Some(&id) = ids.get(&Rc::as_ptr(packed)) {
+            return id;
+        }
+
+        // Registered before the children, which may lead back here.
+        let id = forest.add_node(ForestNode::Symbol(SymbolNode {
+            symbol: self.grammar.symbols().symbol(packed.symbol).clone(),
+            start: packed.start,
+            end: packed.end,
+            packed: Vec::new(),
+        }));
+        ids.insert(Rc::as_ptr(packed), id);
+
+        for (prod, children) in packed.alternatives.borrow().iter() {
+            let children = children
+                .iter()
+                .map(|child| self.forest_node(forest, child, ids))
+                .collect();
+            forest.add_packed(id, PackedNode { prod: *prod, children });
+        }
+        id
+    }
+}
+
+// Every path of `len` edges down from `v`.
+fn paths(nodes: &[GssNode], v: usize, len: usize) -> Vec<Path> {
+    if len == 0 {
+        return vec![Vec::new()];
+    }
+
+    let mut result = Vec::new();
+    for (edge, &(next, _)) in nodes[v].edges.iter().enumerate() {
+        for mut rest in paths(nodes, next, len - 1) {
+            rest.insert(0, (v, edge));
+            result.push(rest);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{LAMBDA, Parser};
+    use super::super::test_util::*;
+
+    #[test]
+    fn ambiguous_test() {
+        let parser = GlrParser::new(sum_grammar());
+        assert!(!Parser::new(sum_grammar()).is_lr1());
+
+        let result = parser.parse(lex("num + num EOF")).unwrap();
+        assert!(!result.is_ambiguous());
+
+        let trees = parser.parse(lex("num + num + num EOF")).unwrap().trees();
+        assert_eq!(trees.len(), 2);
+        let mut shapes: Vec<usize> = trees
+            .iter()
+            .map(|tree| {
+                let root = tree.node(tree.root().unwrap()).unwrap();
+                tree.node(root.children[0]).unwrap().children.len()
+            })
+            .collect();
+        shapes.sort();
+        // (num + num) + num and num + (num + num).
+        assert_eq!(shapes, vec![1, 3]);
+
+        assert_eq!(parser.parse(lex("num + num + num + num EOF")).unwrap().trees().len(), 5);
+    }
+
+    #[test]
+    fn lookahead_test() {
+        // Not LR(1): telling A from B takes two tokens of lookahead.
+        let g = || Grammar::from_str(
+            "S",
+            vec!["S", "A", "B"],
+            vec![
+                ("S", vec!["A", "x", "y"]),
+                ("S", vec!["B", "x", "z"]),
+                ("A", vec!["a"]),
+                ("B", vec!["a"]),
+            ],
+        );
+        assert!(!Parser::new(g()).is_lr1());
+
+        let parser = GlrParser::new(g());
+        for &(input, nt) in &[("a x y EOF", "A"), ("a x z EOF", "B")] {
+            let tree = match parser.parse(lex(input)).unwrap() {
+                GlrResult::Unique(tree) => tree,
+                GlrResult::Ambiguous(_) => panic!("{} is not ambiguous", input),
+            };
+            let root = tree.node(tree.root().unwrap()).unwrap();
+            let first = tree.node(root.children[0]).unwrap();
+            assert_eq!(first.data.to_string(), format!("NT(\"{}\")", nt));
+        }
+
+        match parser.parse(lex("a x x EOF")).err().unwrap() {
+            ParseError::UnexpectedToken { index, ref expected, .. } => {
+                assert_eq!(index, 2);
+                assert_eq!(expected, &["y", "z"]);
+            }
+            err => panic!("Unexpected {:?}", err),
+        }
+        let err = parser.parse(lex("a x")).err().unwrap();
+        assert_eq!(err.to_string(), "expected `y` or `z`, found end of input");
+    }
+
+    #[test]
+    fn lambda_test() {
+        let g = Grammar::from_str(
+            "S",
+            vec!["S", "A"],
+            vec![("S", vec!["A", "S", "b"]), ("S", vec!["x"]), ("A", vec![LAMBDA])],
+        );
+        let parser = GlrParser::new(g);
+
+        let tree = parser.parse(lex("x b b EOF")).unwrap().trees().remove(0);
+        assert_eq!(tree.to_string().matches("NT(\"A\")").count(), 2);
+        assert_eq!(parser.parse(lex("x b b b EOF")).unwrap().trees().len(), 1);
+        assert!(parser.parse(lex("b x EOF")).is_err());
+    }
+}
@@ -0,0 +1,970 @@
+// Generated by lr1 for the grammar 05b5f8131f020413. Do not edit.
+
+use crate::{NodeId, ParseError, Symbol, TokenLike, Tree};
+
+/// Terminal names, indexed by terminal id.
+pub static TERMINALS: [&str; 10] = [
+    "EOF",
+    "(",
+    ")",
+    "*",
+    "+",
+    "-",
+    "/",
+    "LAMBDA",
+    "name",
+    "num",
+];
+
+/// Non terminal names, indexed by non terminal index.
+pub static NON_TERMINALS: [&str; 6] = [
+    "Expr",
+    "Expr'",
+    "FAKE_GOAL",
+    "Factor",
+    "Term",
+    "Term'",
+];
+
+// Left hand side (non terminal index) and length of every production.
+static RULES: [(usize, usize); 12] = [
+    (2, 1),
+    (0, 2),
+    (1, 3),
+    (1, 3),
+    (1, 0),
+    (4, 2),
+    (5, 3),
+    (5, 3),
+    (5, 0),
+    (3, 3),
+    (3, 1),
+    (3, 1),
+];
+
+// Terminal ids with an action, by state.
+static EXPECTED: [&[usize]; 44] = [
+    &[1, 8, 9],
+    &[1, 8, 9],
+    &[0, 3, 4, 5, 6],
+    &[0, 3, 4, 5, 6],
+    &[0],
+    &[0, 3, 4, 5, 6],
+    &[0, 4, 5],
+    &[1, 8, 9],
+    &[2, 3, 4, 5, 6],
+    &[2, 3, 4, 5, 6],
+    &[2],
+    &[2, 3, 4, 5, 6],
+    &[2, 4, 5],
+    &[1, 8, 9],
+    &[1, 8, 9],
+    &[0, 4, 5],
+    &[1, 8, 9],
+    &[1, 8, 9],
+    &[0],
+    &[2],
+    &[0, 3, 4, 5, 6],
+    &[1, 8, 9],
+    &[1, 8, 9],
+    &[2, 4, 5],
+    &[1, 8, 9],
+    &[1, 8, 9],
+    &[2],
+    &[0, 3, 4, 5, 6],
+    &[0, 3, 4, 5, 6],
+    &[0, 4, 5],
+    &[0, 4, 5],
+    &[2, 3, 4, 5, 6],
+    &[2, 3, 4, 5, 6],
+    &[2, 3, 4, 5, 6],
+    &[2, 4, 5],
+    &[2, 4, 5],
+    &[0, 4, 5],
+    &[0, 4, 5],
+    &[0],
+    &[0],
+    &[2, 4, 5],
+    &[2, 4, 5],
+    &[2],
+    &[2],
+];
+
+fn terminal_of(kind: &str) -> Option<usize> {
+    match kind {
+        "EOF" => Some(0),
+        "(" => Some(1),
+        ")" => Some(2),
+        "*" => Some(3),
+        "+" => Some(4),
+        "-" => Some(5),
+        "/" => Some(6),
+        "LAMBDA" => Some(7),
+        "name" => Some(8),
+        "num" => Some(9),
+        _ => None,
+    }
+}
+
+fn expected(state: usize) -> Vec<String> {
+    EXPECTED[state].iter().map(|&t| TERMINALS[t].to_string()).collect()
+}
+
+fn unexpected(index: usize, word: &dyn TokenLike, state: usize) -> ParseError {
+    ParseError::UnexpectedToken {
+        index,
+        kind: word.kind().clone(),
+        lexeme: word.lexeme().clone(),
+        state,
+        expected: expected(state),
+    }
+}
+
+fn unexpected_end(state: usize) -> ParseError {
+    ParseError::UnexpectedEnd {
+        state,
+        expected: expected(state),
+    }
+}
+
+fn token_terminal(index: usize, word: &dyn TokenLike, state: usize) -> Result<usize, ParseError> {
+    terminal_of(word.kind()).ok_or_else(|| unexpected(index, word, state))
+}
+
+enum Ret {
+    Accept,
+    // Production, stack elements left to pop and the popped nodes, last first.
+    Reduce(usize, usize, Vec<NodeId>),
+}
+
+type Goto = fn(&mut Context, usize, NodeId) -> Result<Ret, ParseError>;
+
+struct Context<'a> {
+    tokens: &'a mut dyn Iterator<Item = Box<dyn TokenLike>>,
+    word: Option<Box<dyn TokenLike>>,
+    // Index of `word` in the input.
+    index: usize,
+    terminal: usize,
+    tree: Tree,
+}
+
+impl<'a> Context<'a> {
+    // Shifts the lookahead, going to `state`, and reads the next one.
+    fn shift(&mut self, state: usize) -> Result<NodeId, ParseError> {
+        let word = self.word.take().expect("Shift without a token");
+        let node_id = self.tree.new_node(word);
+
+        let word = self.tokens.next().ok_or_else(|| unexpected_end(state))?;
+        self.index += 1;
+        self.terminal = token_terminal(self.index, &*word, state)?;
+        self.word = Some(word);
+        Ok(node_id)
+    }
+
+    fn unexpected(&self, state: usize) -> ParseError {
+        unexpected(self.index, &**self.word.as_ref().expect("Error without a token"), state)
+    }
+
+    // Either pops the calling state (`node` is its stack element) or, once
+    // every element of the production is popped, builds the non terminal and
+    // moves to the goto state.
+    fn unwind(&mut self, mut ret: Ret, node: Option<NodeId>, goto: Goto) -> Result<Ret, ParseError> {
+        loop {
+            let (prod, left, mut children) = match ret {
+                Ret::Accept => return Ok(Ret::Accept),
+                Ret::Reduce(prod, left, children) => (prod, left, children),
+            };
+
+            if left > 0 {
+                children.push(node.expect("Unexpected EOF"));
+                return Ok(Ret::Reduce(prod, left - 1, children));
+            }
+
+            let from = RULES[prod].0;
+            let new_root = self.tree.new_node(Symbol::NT(NON_TERMINALS[from].to_string()));
+            self.tree.set_root(new_root);
+            for child_id in children.into_iter().rev() {
+                self.tree.append(new_root, child_id);
+            }
+
+            ret = goto(self, from, new_root)?;
+        }
+    }
+}
+
+fn no_goto(_: &mut Context, _: usize, _: NodeId) -> Result<Ret, ParseError> {
+    Err(ParseError::Internal("Next state is empty".to_string()))
+}
+
+pub fn parse<I>(mut tokens: I) -> Result<Tree, ParseError>
+where
+    I: Iterator<Item = Box<dyn TokenLike>>,
+{
+    let word = match tokens.next() {
+        Some(word) => word,
+        None => return Ok(Tree::new()),
+    };
+
+    if word.kind() == "EOF" {
+        return Ok(Tree::new());
+    }
+
+    let terminal = token_terminal(0, &*word, 0)?;
+    let mut ctx = Context {
+        tokens: &mut tokens,
+        word: Some(word),
+        index: 0,
+        terminal,
+        tree: Tree::new(),
+    };
+
+    match state_0(&mut ctx, None)? {
+        Ret::Accept => Ok(ctx.tree),
+        Ret::Reduce(..) => Err(ParseError::Internal("Reduce Error: empty stack".to_string())),
+    }
+}
+
+fn state_0(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(1)?;
+            state_1(ctx, Some(child))?
+        }
+        8 => {
+            let child = ctx.shift(2)?;
+            state_2(ctx, Some(child))?
+        }
+        9 => {
+            let child = ctx.shift(3)?;
+            state_3(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(0)),
+    };
+    ctx.unwind(ret, node, goto_0)
+}
+
+fn goto_0(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        0 => state_4(ctx, Some(node)),
+        3 => state_5(ctx, Some(node)),
+        4 => state_6(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_1(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(7)?;
+            state_7(ctx, Some(child))?
+        }
+        8 => {
+            let child = ctx.shift(8)?;
+            state_8(ctx, Some(child))?
+        }
+        9 => {
+            let child = ctx.shift(9)?;
+            state_9(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(1)),
+    };
+    ctx.unwind(ret, node, goto_1)
+}
+
+fn goto_1(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        0 => state_10(ctx, Some(node)),
+        3 => state_11(ctx, Some(node)),
+        4 => state_12(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_2(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        0 | 3..=6 => Ret::Reduce(11, 1, Vec::new()),
+        _ => return Err(ctx.unexpected(2)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_3(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        0 | 3..=6 => Ret::Reduce(10, 1, Vec::new()),
+        _ => return Err(ctx.unexpected(3)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_4(ctx: &mut Context, _: Option<NodeId>) -> Result<Ret, ParseError> {
+    match ctx.terminal {
+        0 => Ok(Ret::Accept),
+        _ => Err(ctx.unexpected(4)),
+    }
+}
+
+fn state_5(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        3 => {
+            let child = ctx.shift(13)?;
+            state_13(ctx, Some(child))?
+        }
+        6 => {
+            let child = ctx.shift(14)?;
+            state_14(ctx, Some(child))?
+        }
+        0 | 4 | 5 => Ret::Reduce(8, 0, Vec::new()),
+        _ => return Err(ctx.unexpected(5)),
+    };
+    ctx.unwind(ret, node, goto_5)
+}
+
+fn goto_5(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        5 => state_15(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_6(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        4 => {
+            let child = ctx.shift(16)?;
+            state_16(ctx, Some(child))?
+        }
+        5 => {
+            let child = ctx.shift(17)?;
+            state_17(ctx, Some(child))?
+        }
+        0 => Ret::Reduce(4, 0, Vec::new()),
+        _ => return Err(ctx.unexpected(6)),
+    };
+    ctx.unwind(ret, node, goto_6)
+}
+
+fn goto_6(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        1 => state_18(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_7(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(7)?;
+            state_7(ctx, Some(child))?
+        }
+        8 => {
+            let child = ctx.shift(8)?;
+            state_8(ctx, Some(child))?
+        }
+        9 => {
+            let child = ctx.shift(9)?;
+            state_9(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(7)),
+    };
+    ctx.unwind(ret, node, goto_7)
+}
+
+fn goto_7(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        0 => state_19(ctx, Some(node)),
+        3 => state_11(ctx, Some(node)),
+        4 => state_12(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_8(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2..=6 => Ret::Reduce(11, 1, Vec::new()),
+        _ => return Err(ctx.unexpected(8)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_9(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2..=6 => Ret::Reduce(10, 1, Vec::new()),
+        _ => return Err(ctx.unexpected(9)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_10(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2 => {
+            let child = ctx.shift(20)?;
+            state_20(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(10)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_11(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        3 => {
+            let child = ctx.shift(21)?;
+            state_21(ctx, Some(child))?
+        }
+        6 => {
+            let child = ctx.shift(22)?;
+            state_22(ctx, Some(child))?
+        }
+        2 | 4 | 5 => Ret::Reduce(8, 0, Vec::new()),
+        _ => return Err(ctx.unexpected(11)),
+    };
+    ctx.unwind(ret, node, goto_11)
+}
+
+fn goto_11(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        5 => state_23(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_12(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        4 => {
+            let child = ctx.shift(24)?;
+            state_24(ctx, Some(child))?
+        }
+        5 => {
+            let child = ctx.shift(25)?;
+            state_25(ctx, Some(child))?
+        }
+        2 => Ret::Reduce(4, 0, Vec::new()),
+        _ => return Err(ctx.unexpected(12)),
+    };
+    ctx.unwind(ret, node, goto_12)
+}
+
+fn goto_12(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        1 => state_26(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_13(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(1)?;
+            state_1(ctx, Some(child))?
+        }
+        8 => {
+            let child = ctx.shift(2)?;
+            state_2(ctx, Some(child))?
+        }
+        9 => {
+            let child = ctx.shift(3)?;
+            state_3(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(13)),
+    };
+    ctx.unwind(ret, node, goto_13)
+}
+
+fn goto_13(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        3 => state_27(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_14(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(1)?;
+            state_1(ctx, Some(child))?
+        }
+        8 => {
+            let child = ctx.shift(2)?;
+            state_2(ctx, Some(child))?
+        }
+        9 => {
+            let child = ctx.shift(3)?;
+            state_3(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(14)),
+    };
+    ctx.unwind(ret, node, goto_14)
+}
+
+fn goto_14(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        3 => state_28(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_15(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        0 | 4 | 5 => Ret::Reduce(5, 2, Vec::new()),
+        _ => return Err(ctx.unexpected(15)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_16(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(1)?;
+            state_1(ctx, Some(child))?
+        }
+        8 => {
+            let child = ctx.shift(2)?;
+            state_2(ctx, Some(child))?
+        }
+        9 => {
+            let child = ctx.shift(3)?;
+            state_3(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(16)),
+    };
+    ctx.unwind(ret, node, goto_16)
+}
+
+fn goto_16(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        3 => state_5(ctx, Some(node)),
+        4 => state_29(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_17(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(1)?;
+            state_1(ctx, Some(child))?
+        }
+        8 => {
+            let child = ctx.shift(2)?;
+            state_2(ctx, Some(child))?
+        }
+        9 => {
+            let child = ctx.shift(3)?;
+            state_3(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(17)),
+    };
+    ctx.unwind(ret, node, goto_17)
+}
+
+fn goto_17(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        3 => state_5(ctx, Some(node)),
+        4 => state_30(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_18(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        0 => Ret::Reduce(1, 2, Vec::new()),
+        _ => return Err(ctx.unexpected(18)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_19(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2 => {
+            let child = ctx.shift(31)?;
+            state_31(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(19)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_20(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        0 | 3..=6 => Ret::Reduce(9, 3, Vec::new()),
+        _ => return Err(ctx.unexpected(20)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_21(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(7)?;
+            state_7(ctx, Some(child))?
+        }
+        8 => {
+            let child = ctx.shift(8)?;
+            state_8(ctx, Some(child))?
+        }
+        9 => {
+            let child = ctx.shift(9)?;
+            state_9(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(21)),
+    };
+    ctx.unwind(ret, node, goto_21)
+}
+
+fn goto_21(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        3 => state_32(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_22(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(7)?;
+            state_7(ctx, Some(child))?
+        }
+        8 => {
+            let child = ctx.shift(8)?;
+            state_8(ctx, Some(child))?
+        }
+        9 => {
+            let child = ctx.shift(9)?;
+            state_9(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(22)),
+    };
+    ctx.unwind(ret, node, goto_22)
+}
+
+fn goto_22(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        3 => state_33(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_23(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2 | 4 | 5 => Ret::Reduce(5, 2, Vec::new()),
+        _ => return Err(ctx.unexpected(23)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_24(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(7)?;
+            state_7(ctx, Some(child))?
+        }
+        8 => {
+            let child = ctx.shift(8)?;
+            state_8(ctx, Some(child))?
+        }
+        9 => {
+            let child = ctx.shift(9)?;
+            state_9(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(24)),
+    };
+    ctx.unwind(ret, node, goto_24)
+}
+
+fn goto_24(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        3 => state_11(ctx, Some(node)),
+        4 => state_34(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_25(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(7)?;
+            state_7(ctx, Some(child))?
+        }
+        8 => {
+            let child = ctx.shift(8)?;
+            state_8(ctx, Some(child))?
+        }
+        9 => {
+            let child = ctx.shift(9)?;
+            state_9(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(25)),
+    };
+    ctx.unwind(ret, node, goto_25)
+}
+
+fn goto_25(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        3 => state_11(ctx, Some(node)),
+        4 => state_35(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_26(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2 => Ret::Reduce(1, 2, Vec::new()),
+        _ => return Err(ctx.unexpected(26)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_27(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        3 => {
+            let child = ctx.shift(13)?;
+            state_13(ctx, Some(child))?
+        }
+        6 => {
+            let child = ctx.shift(14)?;
+            state_14(ctx, Some(child))?
+        }
+        0 | 4 | 5 => Ret::Reduce(8, 0, Vec::new()),
+        _ => return Err(ctx.unexpected(27)),
+    };
+    ctx.unwind(ret, node, goto_27)
+}
+
+fn goto_27(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        5 => state_36(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_28(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        3 => {
+            let child = ctx.shift(13)?;
+            state_13(ctx, Some(child))?
+        }
+        6 => {
+            let child = ctx.shift(14)?;
+            state_14(ctx, Some(child))?
+        }
+        0 | 4 | 5 => Ret::Reduce(8, 0, Vec::new()),
+        _ => return Err(ctx.unexpected(28)),
+    };
+    ctx.unwind(ret, node, goto_28)
+}
+
+fn goto_28(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        5 => state_37(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_29(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        4 => {
+            let child = ctx.shift(16)?;
+            state_16(ctx, Some(child))?
+        }
+        5 => {
+            let child = ctx.shift(17)?;
+            state_17(ctx, Some(child))?
+        }
+        0 => Ret::Reduce(4, 0, Vec::new()),
+        _ => return Err(ctx.unexpected(29)),
+    };
+    ctx.unwind(ret, node, goto_29)
+}
+
+fn goto_29(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        1 => state_38(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_30(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        4 => {
+            let child = ctx.shift(16)?;
+            state_16(ctx, Some(child))?
+        }
+        5 => {
+            let child = ctx.shift(17)?;
+            state_17(ctx, Some(child))?
+        }
+        0 => Ret::Reduce(4, 0, Vec::new()),
+        _ => return Err(ctx.unexpected(30)),
+    };
+    ctx.unwind(ret, node, goto_30)
+}
+
+fn goto_30(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        1 => state_39(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_31(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2..=6 => Ret::Reduce(9, 3, Vec::new()),
+        _ => return Err(ctx.unexpected(31)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_32(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        3 => {
+            let child = ctx.shift(21)?;
+            state_21(ctx, Some(child))?
+        }
+        6 => {
+            let child = ctx.shift(22)?;
+            state_22(ctx, Some(child))?
+        }
+        2 | 4 | 5 => Ret::Reduce(8, 0, Vec::new()),
+        _ => return Err(ctx.unexpected(32)),
+    };
+    ctx.unwind(ret, node, goto_32)
+}
+
+fn goto_32(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        5 => state_40(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_33(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        3 => {
+            let child = ctx.shift(21)?;
+            state_21(ctx, Some(child))?
+        }
+        6 => {
+            let child = ctx.shift(22)?;
+            state_22(ctx, Some(child))?
+        }
+        2 | 4 | 5 => Ret::Reduce(8, 0, Vec::new()),
+        _ => return Err(ctx.unexpected(33)),
+    };
+    ctx.unwind(ret, node, goto_33)
+}
+
+fn goto_33(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        5 => state_41(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_34(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        4 => {
+            let child = ctx.shift(24)?;
+            state_24(ctx, Some(child))?
+        }
+        5 => {
+            let child = ctx.shift(25)?;
+            state_25(ctx, Some(child))?
+        }
+        2 => Ret::Reduce(4, 0, Vec::new()),
+        _ => return Err(ctx.unexpected(34)),
+    };
+    ctx.unwind(ret, node, goto_34)
+}
+
+fn goto_34(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        1 => state_42(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_35(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        4 => {
+            let child = ctx.shift(24)?;
+            state_24(ctx, Some(child))?
+        }
+        5 => {
+            let child = ctx.shift(25)?;
+            state_25(ctx, Some(child))?
+        }
+        2 => Ret::Reduce(4, 0, Vec::new()),
+        _ => return Err(ctx.unexpected(35)),
+    };
+    ctx.unwind(ret, node, goto_35)
+}
+
+fn goto_35(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        1 => state_43(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_36(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        0 | 4 | 5 => Ret::Reduce(6, 3, Vec::new()),
+        _ => return Err(ctx.unexpected(36)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_37(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        0 | 4 | 5 => Ret::Reduce(7, 3, Vec::new()),
+        _ => return Err(ctx.unexpected(37)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_38(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        0 => Ret::Reduce(2, 3, Vec::new()),
+        _ => return Err(ctx.unexpected(38)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_39(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        0 => Ret::Reduce(3, 3, Vec::new()),
+        _ => return Err(ctx.unexpected(39)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_40(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2 | 4 | 5 => Ret::Reduce(6, 3, Vec::new()),
+        _ => return Err(ctx.unexpected(40)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_41(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2 | 4 | 5 => Ret::Reduce(7, 3, Vec::new()),
+        _ => return Err(ctx.unexpected(41)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_42(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2 => Ret::Reduce(2, 3, Vec::new()),
+        _ => return Err(ctx.unexpected(42)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_43(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2 => Ret::Reduce(3, 3, Vec::new()),
+        _ => return Err(ctx.unexpected(43)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
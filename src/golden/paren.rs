@@ -0,0 +1,154 @@
+// Generated by lr1 for the grammar e27b3f009e7f6ec2. Do not edit.
+
+use crate::{NodeId, ParseError, Symbol, TokenLike, Tree};
+
+/// Terminal names, indexed by terminal id.
+pub static TERMINALS: [&str; 3] = [
+    "EOF",
+    "(",
+    ")",
+];
+
+/// Non terminal names, indexed by non terminal index.
+pub static NON_TERMINALS: [&str; 3] = [
+    "FAKE_GOAL",
+    "List",
+    "Pair",
+];
+
+const TERMINAL_COUNT: usize = 3;
+const NON_TERMINAL_COUNT: usize = 3;
+const NO_STATE: u32 = 4294967295;
+
+// Left hand side (non terminal index) and length of every production.
+static RULES: [(usize, usize); 5] = [
+    (0, 1),
+    (1, 2),
+    (1, 1),
+    (2, 3),
+    (2, 2),
+];
+
+// Action cells by state and terminal id: 0 is an error, 4 accepts,
+// `state << 2 | 1` shifts and `production << 2 | 2` reduces.
+static ACTION: [u32; 36] = [
+    0, 5, 0, 0, 17, 21, 4, 5, 0, 10, 10, 0, 0, 17, 33, 18,
+    18, 0, 0, 0, 41, 6, 6, 0, 0, 0, 18, 0, 0, 45, 14, 14,
+    0, 0, 0, 14,
+];
+
+// Goto by state and non terminal index, `NO_STATE` if missing.
+static GOTO: [u32; 36] = [
+    NO_STATE, 2, 3, NO_STATE, NO_STATE, 6, NO_STATE, NO_STATE, 7, NO_STATE, NO_STATE, NO_STATE, NO_STATE, NO_STATE, 9, NO_STATE,
+    NO_STATE, NO_STATE, NO_STATE, NO_STATE, NO_STATE, NO_STATE, NO_STATE, NO_STATE, NO_STATE, NO_STATE, NO_STATE, NO_STATE, NO_STATE, NO_STATE, NO_STATE, NO_STATE,
+    NO_STATE, NO_STATE, NO_STATE, NO_STATE,
+];
+
+// Terminal ids with an action, by state.
+static EXPECTED: [&[usize]; 12] = [
+    &[1],
+    &[1, 2],
+    &[0, 1],
+    &[0, 1],
+    &[1, 2],
+    &[0, 1],
+    &[2],
+    &[0, 1],
+    &[2],
+    &[2],
+    &[0, 1],
+    &[2],
+];
+
+fn terminal_of(kind: &str) -> Option<usize> {
+    match kind {
+        "EOF" => Some(0),
+        "(" => Some(1),
+        ")" => Some(2),
+        _ => None,
+    }
+}
+
+fn expected(state: usize) -> Vec<String> {
+    EXPECTED[state].iter().map(|&t| TERMINALS[t].to_string()).collect()
+}
+
+fn unexpected(index: usize, word: &dyn TokenLike, state: usize) -> ParseError {
+    ParseError::UnexpectedToken {
+        index,
+        kind: word.kind().clone(),
+        lexeme: word.lexeme().clone(),
+        state,
+        expected: expected(state),
+    }
+}
+
+fn unexpected_end(state: usize) -> ParseError {
+    ParseError::UnexpectedEnd {
+        state,
+        expected: expected(state),
+    }
+}
+
+fn token_terminal(index: usize, word: &dyn TokenLike, state: usize) -> Result<usize, ParseError> {
+    terminal_of(word.kind()).ok_or_else(|| unexpected(index, word, state))
+}
+
+pub fn parse<I>(mut tokens: I) -> Result<Tree, ParseError>
+where
+    I: Iterator<Item = Box<dyn TokenLike>>,
+{
+    let mut tree = Tree::new();
+    let mut stack: Vec<(usize, Option<NodeId>)> = vec![(0, None)];
+
+    let mut word = match tokens.next() {
+        Some(word) => word,
+        None => return Ok(tree),
+    };
+
+    if word.kind() == "EOF" {
+        return Ok(tree);
+    }
+
+    let mut index = 0;
+    let mut terminal = token_terminal(index, &*word, 0)?;
+
+    loop {
+        let state = stack[stack.len() - 1].0;
+        let cell = ACTION[state * TERMINAL_COUNT + terminal];
+
+        match cell & 3 {
+            1 => {
+                let next = (cell >> 2) as usize;
+                let node_id = tree.new_node(word);
+                stack.push((next, Some(node_id)));
+
+                word = tokens.next().ok_or_else(|| unexpected_end(next))?;
+                index += 1;
+                terminal = token_terminal(index, &*word, next)?;
+            }
+
+            2 => {
+                let (from, len) = RULES[(cell >> 2) as usize];
+                let new_root = tree.new_node(Symbol::NT(NON_TERMINALS[from].to_string()));
+                tree.set_root(new_root);
+
+                let popped = stack.split_off(stack.len() - len);
+                for (_, child_id) in popped {
+                    tree.append(new_root, child_id.expect("Unexpected EOF"));
+                }
+
+                let state = stack[stack.len() - 1].0;
+                let next = GOTO[state * NON_TERMINAL_COUNT + from];
+                if next == NO_STATE {
+                    return Err(ParseError::Internal("Next state is empty".to_string()));
+                }
+                stack.push((next as usize, Some(new_root)));
+            }
+
+            _ if cell == 4 => return Ok(tree),
+
+            _ => return Err(unexpected(index, &*word, state)),
+        }
+    }
+}
@@ -0,0 +1,321 @@
+// Generated by lr1 for the grammar e27b3f009e7f6ec2. Do not edit.
+
+use crate::{NodeId, ParseError, Symbol, TokenLike, Tree};
+
+/// Terminal names, indexed by terminal id.
+pub static TERMINALS: [&str; 3] = [
+    "EOF",
+    "(",
+    ")",
+];
+
+/// Non terminal names, indexed by non terminal index.
+pub static NON_TERMINALS: [&str; 3] = [
+    "FAKE_GOAL",
+    "List",
+    "Pair",
+];
+
+// Left hand side (non terminal index) and length of every production.
+static RULES: [(usize, usize); 5] = [
+    (0, 1),
+    (1, 2),
+    (1, 1),
+    (2, 3),
+    (2, 2),
+];
+
+// Terminal ids with an action, by state.
+static EXPECTED: [&[usize]; 12] = [
+    &[1],
+    &[1, 2],
+    &[0, 1],
+    &[0, 1],
+    &[1, 2],
+    &[0, 1],
+    &[2],
+    &[0, 1],
+    &[2],
+    &[2],
+    &[0, 1],
+    &[2],
+];
+
+fn terminal_of(kind: &str) -> Option<usize> {
+    match kind {
+        "EOF" => Some(0),
+        "(" => Some(1),
+        ")" => Some(2),
+        _ => None,
+    }
+}
+
+fn expected(state: usize) -> Vec<String> {
+    EXPECTED[state].iter().map(|&t| TERMINALS[t].to_string()).collect()
+}
+
+fn unexpected(index: usize, word: &dyn TokenLike, state: usize) -> ParseError {
+    ParseError::UnexpectedToken {
+        index,
+        kind: word.kind().clone(),
+        lexeme: word.lexeme().clone(),
+        state,
+        expected: expected(state),
+    }
+}
+
+fn unexpected_end(state: usize) -> ParseError {
+    ParseError::UnexpectedEnd {
+        state,
+        expected: expected(state),
+    }
+}
+
+fn token_terminal(index: usize, word: &dyn TokenLike, state: usize) -> Result<usize, ParseError> {
+    terminal_of(word.kind()).ok_or_else(|| unexpected(index, word, state))
+}
+
+enum Ret {
+    Accept,
+    // Production, stack elements left to pop and the popped nodes, last first.
+    Reduce(usize, usize, Vec<NodeId>),
+}
+
+type Goto = fn(&mut Context, usize, NodeId) -> Result<Ret, ParseError>;
+
+struct Context<'a> {
+    tokens: &'a mut dyn Iterator<Item = Box<dyn TokenLike>>,
+    word: Option<Box<dyn TokenLike>>,
+    // Index of `word` in the input.
+    index: usize,
+    terminal: usize,
+    tree: Tree,
+}
+
+impl<'a> Context<'a> {
+    // Shifts the lookahead, going to `state`, and reads the next one.
+    fn shift(&mut self, state: usize) -> Result<NodeId, ParseError> {
+        let word = self.word.take().expect("Shift without a token");
+        let node_id = self.tree.new_node(word);
+
+        let word = self.tokens.next().ok_or_else(|| unexpected_end(state))?;
+        self.index += 1;
+        self.terminal = token_terminal(self.index, &*word, state)?;
+        self.word = Some(word);
+        Ok(node_id)
+    }
+
+    fn unexpected(&self, state: usize) -> ParseError {
+        unexpected(self.index, &**self.word.as_ref().expect("Error without a token"), state)
+    }
+
+    // Either pops the calling state (`node` is its stack element) or, once
+    // every element of the production is popped, builds the non terminal and
+    // moves to the goto state.
+    fn unwind(&mut self, mut ret: Ret, node: Option<NodeId>, goto: Goto) -> Result<Ret, ParseError> {
+        loop {
+            let (prod, left, mut children) = match ret {
+                Ret::Accept => return Ok(Ret::Accept),
+                Ret::Reduce(prod, left, children) => (prod, left, children),
+            };
+
+            if left > 0 {
+                children.push(node.expect("Unexpected EOF"));
+                return Ok(Ret::Reduce(prod, left - 1, children));
+            }
+
+            let from = RULES[prod].0;
+            let new_root = self.tree.new_node(Symbol::NT(NON_TERMINALS[from].to_string()));
+            self.tree.set_root(new_root);
+            for child_id in children.into_iter().rev() {
+                self.tree.append(new_root, child_id);
+            }
+
+            ret = goto(self, from, new_root)?;
+        }
+    }
+}
+
+fn no_goto(_: &mut Context, _: usize, _: NodeId) -> Result<Ret, ParseError> {
+    Err(ParseError::Internal("Next state is empty".to_string()))
+}
+
+pub fn parse<I>(mut tokens: I) -> Result<Tree, ParseError>
+where
+    I: Iterator<Item = Box<dyn TokenLike>>,
+{
+    let word = match tokens.next() {
+        Some(word) => word,
+        None => return Ok(Tree::new()),
+    };
+
+    if word.kind() == "EOF" {
+        return Ok(Tree::new());
+    }
+
+    let terminal = token_terminal(0, &*word, 0)?;
+    let mut ctx = Context {
+        tokens: &mut tokens,
+        word: Some(word),
+        index: 0,
+        terminal,
+        tree: Tree::new(),
+    };
+
+    match state_0(&mut ctx, None)? {
+        Ret::Accept => Ok(ctx.tree),
+        Ret::Reduce(..) => Err(ParseError::Internal("Reduce Error: empty stack".to_string())),
+    }
+}
+
+fn state_0(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(1)?;
+            state_1(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(0)),
+    };
+    ctx.unwind(ret, node, goto_0)
+}
+
+fn goto_0(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        1 => state_2(ctx, Some(node)),
+        2 => state_3(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_1(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(4)?;
+            state_4(ctx, Some(child))?
+        }
+        2 => {
+            let child = ctx.shift(5)?;
+            state_5(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(1)),
+    };
+    ctx.unwind(ret, node, goto_1)
+}
+
+fn goto_1(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        2 => state_6(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_2(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(1)?;
+            state_1(ctx, Some(child))?
+        }
+        0 => return Ok(Ret::Accept),
+        _ => return Err(ctx.unexpected(2)),
+    };
+    ctx.unwind(ret, node, goto_2)
+}
+
+fn goto_2(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        2 => state_7(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_3(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        0 | 1 => Ret::Reduce(2, 1, Vec::new()),
+        _ => return Err(ctx.unexpected(3)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_4(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        1 => {
+            let child = ctx.shift(4)?;
+            state_4(ctx, Some(child))?
+        }
+        2 => {
+            let child = ctx.shift(8)?;
+            state_8(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(4)),
+    };
+    ctx.unwind(ret, node, goto_4)
+}
+
+fn goto_4(ctx: &mut Context, from: usize, node: NodeId) -> Result<Ret, ParseError> {
+    match from {
+        2 => state_9(ctx, Some(node)),
+        _ => no_goto(ctx, from, node),
+    }
+}
+
+fn state_5(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        0 | 1 => Ret::Reduce(4, 2, Vec::new()),
+        _ => return Err(ctx.unexpected(5)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_6(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2 => {
+            let child = ctx.shift(10)?;
+            state_10(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(6)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_7(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        0 | 1 => Ret::Reduce(1, 2, Vec::new()),
+        _ => return Err(ctx.unexpected(7)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_8(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2 => Ret::Reduce(4, 2, Vec::new()),
+        _ => return Err(ctx.unexpected(8)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_9(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2 => {
+            let child = ctx.shift(11)?;
+            state_11(ctx, Some(child))?
+        }
+        _ => return Err(ctx.unexpected(9)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_10(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        0 | 1 => Ret::Reduce(3, 3, Vec::new()),
+        _ => return Err(ctx.unexpected(10)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
+
+fn state_11(ctx: &mut Context, node: Option<NodeId>) -> Result<Ret, ParseError> {
+    let ret = match ctx.terminal {
+        2 => Ret::Reduce(3, 3, Vec::new()),
+        _ => return Err(ctx.unexpected(11)),
+    };
+    ctx.unwind(ret, node, no_goto)
+}
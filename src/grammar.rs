@@ -1,15 +1,29 @@
 use std::collections::{HashMap, BTreeSet};
-use std::rc::Rc;
+use std::sync::Arc;
 
-use super::{FAKE_GOAL, Symbol, Production};
+use super::{FAKE_GOAL, LAMBDA, Symbol, SymbolId, SymbolTable, Production, Rule, BitSet, Assoc};
 
 #[derive(Debug)]
 pub struct Grammar {
     pub goal: Symbol,
-    pub productions: Vec<Rc<Production>>,
-    prod_map: HashMap<Symbol, Vec<Rc<Production>>>,
-    first_map: HashMap<Symbol, BTreeSet<Symbol>>,
-    symbols: BTreeSet<Symbol>,
+    pub productions: Vec<Arc<Production>>,
+    prod_map: HashMap<Symbol, Vec<Arc<Production>>>,
+
+    symbols: SymbolTable,
+    rules: Vec<Rule>,
+    // Production indices grouped by left hand side, indexed by non terminal.
+    prods_of: Vec<Vec<usize>>,
+    first: Vec<(BitSet, bool)>,
+
+    // Precedence level (higher binds tighter) and associativity by terminal
+    // name, and `%prec` overrides by production index. Names don't need to
+    // be terminals of the grammar, so that `%prec UMINUS` works.
+    precedence: HashMap<String, (usize, Assoc)>,
+    prec_levels: usize,
+    prod_precedence: HashMap<usize, String>,
+
+    // Number of shift/reduce and reduce/reduce conflicts declared as expected.
+    expected_conflicts: Option<(usize, usize)>,
 }
 
 impl Grammar {
@@ -38,7 +52,7 @@ impl Grammar {
                     })
                     .collect();
 
-                Rc::new(Production::new(from, to))
+                Arc::new(Production::new(from, to))
             })
             .collect();
 
@@ -50,43 +64,68 @@ impl Grammar {
         Grammar::new(goal, prods)
     }
 
-    pub fn new(goal: Symbol, prods: Vec<Rc<Production>>) -> Grammar {
+    pub fn new(goal: Symbol, prods: Vec<Arc<Production>>) -> Grammar {
         assert!(goal.is_non_terminal(), "Unexpected terminal goal");
 
         let mut prod_map = HashMap::new();
-        let mut symbols = {
-            let mut set = BTreeSet::new();
-            set.insert(goal.clone());
-            set
-        };
+        let mut symbols = BTreeSet::new();
+        symbols.insert(goal.clone());
 
         for prod in &prods {
             assert!(
                 prod.from.is_non_terminal(),
                 "Unexpected terminal in prod.from"
             );
-            prod_map.entry(prod.from.clone()).or_insert(vec![]).push(
-                prod.clone(),
-            );
+            prod_map
+                .entry(prod.from.clone())
+                .or_insert_with(Vec::new)
+                .push(prod.clone());
             symbols.insert(prod.from.clone());
             for s in &prod.to {
                 symbols.insert(s.clone());
             }
         }
 
+        let symbols = SymbolTable::new(&symbols);
+        let lambda = Symbol::lambda();
+        let rules: Vec<Rule> = prods
+            .iter()
+            .map(|prod| Rule {
+                from: symbols.id(&prod.from).unwrap(),
+                to: prod.to
+                    .iter()
+                    .filter(|s| **s != lambda)
+                    .map(|s| symbols.id(s).unwrap())
+                    .collect(),
+            })
+            .collect();
+
+        let mut prods_of = vec![Vec::new(); symbols.non_terminal_count()];
+        for (i, rule) in rules.iter().enumerate() {
+            prods_of[symbols.non_terminal_index(rule.from)].push(i);
+        }
+
         let mut grammar = Grammar {
-            goal: goal,
+            goal,
             productions: prods,
-            prod_map: prod_map,
-            symbols: symbols,
-            first_map: HashMap::new(),
+            prod_map,
+            symbols,
+            rules,
+            prods_of,
+            first: Vec::new(),
+
+            precedence: HashMap::new(),
+            prec_levels: 0,
+            prod_precedence: HashMap::new(),
+
+            expected_conflicts: None,
         };
 
-        grammar.first_map = grammar.calc_first();
+        grammar.first = grammar.calc_first();
         grammar
     }
 
-    pub fn get_prods(&self, from: &Symbol) -> Option<&Vec<Rc<Production>>> {
+    pub fn get_prods(&self, from: &Symbol) -> Option<&Vec<Arc<Production>>> {
         if from.is_terminal() {
             return None;
         }
@@ -94,112 +133,222 @@ impl Grammar {
         self.prod_map.get(from)
     }
 
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// The interned productions, in the same order as `productions`.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Indices of the productions whose left hand side is `from`.
+    pub fn prods_of(&self, from: SymbolId) -> &[usize] {
+        if self.symbols.is_terminal(from) {
+            return &[];
+        }
+
+        &self.prods_of[self.symbols.non_terminal_index(from)]
+    }
+
     pub fn terminals(&self) -> BTreeSet<Symbol> {
         self.symbols
-            .iter()
-            .filter(|s| s.is_terminal())
-            .cloned()
+            .terminals()
+            .into_iter()
+            .skip(1)
+            .map(|t| self.symbols.symbol(t).clone())
             .collect()
     }
 
     pub fn non_terminals(&self) -> BTreeSet<Symbol> {
         self.symbols
-            .iter()
-            .filter(|s| s.is_non_terminal())
-            .cloned()
+            .non_terminals()
+            .into_iter()
+            .map(|nt| self.symbols.symbol(nt).clone())
             .collect()
     }
 
-    fn calc_first(&self) -> HashMap<Symbol, BTreeSet<Symbol>> {
-        let mut first_map: HashMap<Symbol, BTreeSet<Symbol>> = HashMap::new();
-        let mut first_map_snapshot = HashMap::new();
-
-        let lambda_set = vec![Symbol::lambda()].into_iter().collect();
-        let specials = vec![Symbol::eof(), Symbol::lambda()].into_iter().collect();
-
-        for t in self.terminals().union(&specials) {
-            first_map.insert(t.clone(), vec![t.clone()].into_iter().collect());
+    // FIRST of every symbol as a set of terminal ids, plus whether the symbol
+    // derives LAMBDA.
+    fn calc_first(&self) -> Vec<(BitSet, bool)> {
+        let mut first = vec![(BitSet::new(), false); self.symbols.len()];
+        for t in self.symbols.terminals() {
+            if self.symbols.symbol(t).as_str() == LAMBDA {
+                first[t.index()].1 = true;
+            } else {
+                first[t.index()].0.insert(t.index());
+            }
         }
 
-        for nt in &self.non_terminals() {
-            first_map.insert(nt.clone(), vec![].into_iter().collect());
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in &self.rules {
+                let (set, nullable) = self.first_of_ids_with(&first, &rule.to);
+                let entry = &mut first[rule.from.index()];
+                changed |= entry.0.union_with(&set);
+                if nullable && !entry.1 {
+                    entry.1 = true;
+                    changed = true;
+                }
+            }
         }
 
-        while first_map != first_map_snapshot {
-            first_map_snapshot = first_map.clone();
-            for prod in &self.productions {
+        first
+    }
 
-                let rhs = prod.to
-                    .iter()
-                    .enumerate()
-                    .take_while(|&(i, _)| {
-                        i == 0 ||
-                            first_map.get(&prod.to[i - 1]).unwrap().contains(
-                                &Symbol::lambda(),
-                            )
-                    })
-                    .fold(BTreeSet::new(), |acc, (i, symbol)| {
-                        let first_i = first_map.get(symbol).expect("Wrong symbol");
-                        let next = if i == prod.to.len() - 1 {
-                            first_i.iter().cloned().collect()
-                        } else {
-                            first_i.difference(&lambda_set).cloned().collect()
-                        };
-
-                        acc.union(&next).cloned().collect()
-                    });
-
-                if let Some(first) = first_map.get_mut(&prod.from) {
-                    *first = first.union(&rhs).cloned().collect();
-                }
+    fn first_of_ids_with(&self, first: &[(BitSet, bool)], symbols: &[SymbolId]) -> (BitSet, bool) {
+        let mut set = BitSet::new();
+        for s in symbols {
+            let (ref first_s, nullable) = first[s.index()];
+            set.union_with(first_s);
+            if !nullable {
+                return (set, false);
             }
         }
 
-        first_map
+        (set, true)
     }
 
+    /// FIRST of a sequence of symbols as terminal ids, and whether the whole
+    /// sequence derives LAMBDA.
+    pub fn first_of_ids(&self, symbols: &[SymbolId]) -> (BitSet, bool) {
+        self.first_of_ids_with(&self.first, symbols)
+    }
 
-    // TODO (potentially) this is a copy paste logic of what happens inside the calc_first
-    // can we abstract that?
-    pub fn first_of(&self, symbols: &Vec<Symbol>) -> Option<BTreeSet<Symbol>> {
-        let lambda_set = vec![Symbol::lambda()].into_iter().collect();
+    /// FIRST of a single symbol, with `LAMBDA` included if it is nullable.
+    pub fn first(&self, symbol: &Symbol) -> Option<BTreeSet<Symbol>> {
+        self.first_of(::std::slice::from_ref(symbol))
+    }
 
-        let first = symbols
+    pub fn first_of(&self, symbols: &[Symbol]) -> Option<BTreeSet<Symbol>> {
+        let lambda = Symbol::lambda();
+        let ids: Vec<SymbolId> = symbols
             .iter()
-            .enumerate()
-            .take_while(|&(i, _)| {
-                i == 0 ||
-                    self.first_map.get(&symbols[i - 1]).unwrap().contains(
-                        &Symbol::lambda(),
-                    )
-            })
-            .fold(BTreeSet::new(), |acc, (i, symbol)| {
-                let first_i = self.first_map.get(symbol).expect("Wrong symbol");
-                let next = if i == symbols.len() - 1 {
-                    first_i.iter().cloned().collect()
-                } else {
-                    first_i.difference(&lambda_set).cloned().collect()
-                };
+            .filter(|s| **s != lambda)
+            .map(|s| self.symbols.id(s).expect("Wrong symbol"))
+            .collect();
 
-                acc.union(&next).cloned().collect()
-            });
+        let (set, nullable) = self.first_of_ids(&ids);
+        let mut first: BTreeSet<Symbol> = set.iter()
+            .map(|t| self.symbols.symbol(SymbolId(t as u32)).clone())
+            .collect();
+        if nullable && !symbols.is_empty() {
+            first.insert(lambda);
+        }
 
         if first.is_empty() { None } else { Some(first) }
     }
 
+    /// A 64 bit FNV-1a hash of the goal and the productions, in order.
+    ///
+    /// Stable across runs and platforms, so it can be stored next to a
+    /// compiled table to detect that the grammar changed.
+    pub fn fingerprint(&self) -> u64 {
+        fn push_symbol(bytes: &mut Vec<u8>, s: &Symbol) {
+            bytes.push(if s.is_terminal() { b'T' } else { b'N' });
+            bytes.extend_from_slice(s.as_str().as_bytes());
+            bytes.push(0);
+        }
+
+        let mut bytes = Vec::new();
+        push_symbol(&mut bytes, &self.goal);
+        for prod in &self.productions {
+            push_symbol(&mut bytes, &prod.from);
+            for s in &prod.to {
+                push_symbol(&mut bytes, s);
+            }
+            bytes.push(1);
+        }
+
+        // Nothing is added without declarations, so fingerprints of grammars
+        // without precedences are unaffected.
+        let mut levels: Vec<(&String, &(usize, Assoc))> = self.precedence.iter().collect();
+        levels.sort();
+        for (name, &(level, assoc)) in levels {
+            bytes.extend_from_slice(format!("P{}\0{}\0{:?}\0", name, level, assoc).as_bytes());
+        }
+        let mut overrides: Vec<(&usize, &String)> = self.prod_precedence.iter().collect();
+        overrides.sort();
+        for (prod, name) in overrides {
+            bytes.extend_from_slice(format!("O{}\0{}\0", prod, name).as_bytes());
+        }
+        if let Some((shift_reduce, reduce_reduce)) = self.expected_conflicts {
+            bytes.extend_from_slice(format!("E{}\0{}\0", shift_reduce, reduce_reduce).as_bytes());
+        }
+
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+            (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// Declares a new precedence level, higher than every previous one, like
+    /// a `%left`, `%right` or `%nonassoc` line in yacc.
+    pub fn declare_precedence(&mut self, assoc: Assoc, terminals: &[&str]) {
+        self.prec_levels += 1;
+        for terminal in terminals {
+            self.precedence.insert(terminal.to_string(), (self.prec_levels, assoc));
+        }
+    }
+
+    /// Gives the production `prod` (an index into `productions`) the
+    /// precedence of `terminal`, like `%prec` in yacc.
+    pub fn set_production_precedence(&mut self, prod: usize, terminal: &str) {
+        assert!(prod < self.productions.len(), "Unexpected production index");
+        self.prod_precedence.insert(prod, terminal.to_string());
+    }
+
+    /// Declares how many conflicts the grammar is expected to have, like
+    /// `%expect` and `%expect-rr` in bison. Parsers built from the grammar
+    /// then resolve every conflict by default: shift over reduce, and the
+    /// earliest production among reductions.
+    pub fn expect_conflicts(&mut self, shift_reduce: usize, reduce_reduce: usize) {
+        self.expected_conflicts = Some((shift_reduce, reduce_reduce));
+    }
+
+    pub fn expected_conflicts(&self) -> Option<(usize, usize)> {
+        self.expected_conflicts
+    }
+
+    pub fn terminal_precedence(&self, terminal: SymbolId) -> Option<(usize, Assoc)> {
+        self.precedence.get(self.symbols.symbol(terminal).as_str()).cloned()
+    }
+
+    /// The `%prec` override of `prod` or else the precedence of its last
+    /// terminal, if any.
+    pub fn production_precedence(&self, prod: usize) -> Option<(usize, Assoc)> {
+        if let Some(name) = self.prod_precedence.get(&prod) {
+            return self.precedence.get(name).cloned();
+        }
+
+        self.rules[prod]
+            .to
+            .iter()
+            .rev()
+            .find(|&&s| self.symbols.is_terminal(s))
+            .and_then(|&s| self.terminal_precedence(s))
+    }
+
     pub fn with_fake_goal(&self) -> Grammar {
         let fake_goal = Symbol::new_nt(FAKE_GOAL);
         let fake_prod = Production::new(fake_goal.clone(), vec![self.goal.clone()]);
-        let prods = [vec![Rc::new(fake_prod)], self.productions.clone()].concat();
+        let prods = [vec![Arc::new(fake_prod)], self.productions.clone()].concat();
 
-        Grammar::new(fake_goal, prods)
+        let mut g = Grammar::new(fake_goal, prods);
+        g.precedence = self.precedence.clone();
+        g.prec_levels = self.prec_levels;
+        g.prod_precedence = self.prod_precedence
+            .iter()
+            .map(|(&prod, name)| (prod + 1, name.clone()))
+            .collect();
+        g.expected_conflicts = self.expected_conflicts;
+        g
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::super::LAMBDA;
     fn example_grammar() -> Grammar {
         let non_terminals = vec!["Goal", "Expr", "Expr'", "Term", "Term'", "Factor"];
 
@@ -236,7 +385,7 @@ mod tests {
             vec!["+", "-", "x", "%", LAMBDA, "(", ")", "num", "name"]
                 .into_iter()
                 .map(|s| s.to_string())
-                .map(|s| T(s))
+                .map(T)
                 .collect()
         );
 
@@ -245,7 +394,7 @@ mod tests {
             vec!["Goal", "Expr", "Expr'", "Term", "Term'", "Factor"]
                 .into_iter()
                 .map(|s| s.to_string())
-                .map(|s| NT(s))
+                .map(NT)
                 .collect()
         );
 
@@ -257,7 +406,7 @@ mod tests {
 
         for t in &g.terminals() {
             assert_eq!(
-                g.first_map.get(t).unwrap(),
+                &g.first(t).unwrap(),
                 &vec![t.clone()].into_iter().collect()
             );
         }
@@ -277,12 +426,12 @@ mod tests {
             ("Factor", vec!["(", "name", "num"]),
         ];
 
-        for &(ref nt, ref first) in &cases {
-            let actual = g.first_map.get(&NT(nt.to_string())).unwrap();
+        for (nt, first) in &cases {
+            let actual = &g.first(&NT(nt.to_string())).unwrap();
             let expected = first
-                .into_iter()
+                .iter()
                 .map(|s| s.to_string())
-                .map(|s| T(s))
+                .map(T)
                 .collect::<BTreeSet<Symbol>>();
 
             assert_eq!(
@@ -302,12 +451,12 @@ mod tests {
         use Symbol::*;
         let g = example_grammar();
         assert_eq!(
-            g.first_of(&vec![NT("Expr'".to_string()), T("x".to_string())])
+            g.first_of(&[NT("Expr'".to_string()), T("x".to_string())])
                 .unwrap(),
             vec!["+", "-", "x"]
                 .into_iter()
                 .map(|s| s.to_string())
-                .map(|s| T(s))
+                .map(T)
                 .collect::<BTreeSet<Symbol>>()
         )
     }
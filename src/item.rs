@@ -1,13 +1,16 @@
 use std::collections::BTreeSet;
 use std::rc::Rc;
-use std::fmt;
 
-use super::{FAKE_GOAL, Production, Symbol, Grammar};
+use super::{FAKE_GOAL, Symbol, SymbolId, Grammar};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// An LR(1) item with a single lookahead.
+///
+/// `prod` is an index into the grammar's productions, so most operations need
+/// the `Grammar` the item belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Item {
-    pub prod: Rc<Production>,
-    pub lookahead: Symbol,
+    pub prod: usize,
+    pub lookahead: SymbolId,
     pub stacktop: usize,
 }
 
@@ -31,7 +34,7 @@ impl Item {
         );
 
         let from = Symbol::NT(from);
-        let to = to.into_iter()
+        let to: Vec<Symbol> = to.into_iter()
             .map(|s| s.into())
             .map(|s| if non_terminals.contains(&s) {
                 Symbol::NT(s)
@@ -47,97 +50,63 @@ impl Item {
             Symbol::T(lookahead)
         };
 
-        let prod = Production::new(from, to);
-        Item::new(Rc::new(prod), stacktop, lookahead)
+        let prod = g.productions
+            .iter()
+            .position(|prod| prod.from == from && prod.to == to)
+            .expect("Production not found in the grammar");
+        let lookahead = g.symbols().id(&lookahead).expect(
+            "Lookahead not found in the grammar",
+        );
+        Item::new(prod, stacktop, lookahead)
     }
 
-    pub fn new(prod: Rc<Production>, stacktop: usize, lookahead: Symbol) -> Item {
+    pub fn new(prod: usize, stacktop: usize, lookahead: SymbolId) -> Item {
         Item {
-            prod: prod,
-            stacktop: stacktop,
-            lookahead: lookahead,
+            prod,
+            stacktop,
+            lookahead,
         }
     }
 
-    pub fn from_production(prod: Rc<Production>, lookahead: Symbol) -> Item {
-        Item::new(prod, 0, lookahead)
-    }
-
-    pub fn set_to_string(items: &BTreeSet<Item>) -> String {
+    pub fn set_to_string(items: &BTreeSet<Item>, g: &Grammar) -> String {
         items
             .iter()
-            .map(|item| format!("{}", item))
+            .map(|item| item.to_string(g))
             .collect::<Vec<String>>()
             .join(" ")
     }
 
-    pub fn set_of_sets_to_string(set: &BTreeSet<Rc<BTreeSet<Item>>>) -> String {
+    pub fn set_of_sets_to_string(set: &BTreeSet<Rc<BTreeSet<Item>>>, g: &Grammar) -> String {
         set.iter()
-            .map(|cc_i| Item::set_to_string(cc_i))
+            .map(|cc_i| Item::set_to_string(cc_i, g))
             .collect::<Vec<String>>()
             .join("\n")
     }
 
-    pub fn is_complete(&self) -> bool {
-        assert!(
-            self.stacktop <= self.prod.to.len(),
-            "Stacktop out of bounds"
-        );
-        if self.stacktop == self.prod.to.len() {
-            true
-        } else {
-            false
-        }
+    pub fn is_complete(&self, g: &Grammar) -> bool {
+        let len = g.rules()[self.prod].to.len();
+        assert!(self.stacktop <= len, "Stacktop out of bounds");
+        self.stacktop == len
     }
 
-    pub fn is_terminator(&self) -> bool {
-        self.prod.from.as_str() == FAKE_GOAL && self.stacktop == 1 && self.is_complete()
+    pub fn is_terminator(&self, g: &Grammar) -> bool {
+        g.productions[self.prod].from.as_str() == FAKE_GOAL && self.stacktop == 1 &&
+            self.is_complete(g)
     }
 
-    pub fn stacktop(&self) -> Option<&Symbol> {
-        if self.stacktop == self.prod.to.len() {
-            // Item complete
-            return None;
-        } else if self.stacktop < self.prod.to.len() {
-            return self.prod.to.get(self.stacktop);
-        } else {
-            panic!("Stacktop out of bounds")
-        }
+    pub fn stacktop(&self, g: &Grammar) -> Option<SymbolId> {
+        let to = &g.rules()[self.prod].to;
+        assert!(self.stacktop <= to.len(), "Stacktop out of bounds");
+        to.get(self.stacktop).cloned()
     }
 
-    pub fn after_stacktop(&self) -> &[Symbol] {
-        &self.prod.to[self.stacktop + 1..]
-    }
-
-    pub fn after_stacktop_and_lookahead(&self) -> Vec<Symbol> {
-        let head = self.after_stacktop();
-        let tail = &[self.lookahead.clone()];
-        head.iter().chain(tail.iter()).cloned().collect()
-    }
-
-    pub fn clone_with_next_stacktop(&self) -> Item {
-        let mut item = self.clone();
-        if item.is_complete() {
-            panic!(
-                "Attempting to push item's stacktop when the item is already complete {:?}",
-                self
-            );
-        }
-
-        item.stacktop += 1;
-        item
-    }
-}
-
-impl fmt::Display for Item {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let to: Vec<String> = self.prod
-            .to
+    pub fn to_string(&self, g: &Grammar) -> String {
+        let rule = &g.rules()[self.prod];
+        let to: Vec<&str> = rule.to
             .iter()
-            .map(|s| s.to_string())
-            .cloned()
+            .map(|s| g.symbols().symbol(*s).as_str())
             .collect();
-        let to_str: String = if self.stacktop() == None {
+        let to_str: String = if self.stacktop == to.len() {
             format!("{} •", to.join(" "))
         } else {
             to.iter()
@@ -145,12 +114,17 @@ impl fmt::Display for Item {
                 .map(|(i, s)| if i == self.stacktop {
                     format!("• {}", s)
                 } else {
-                    s.clone()
+                    s.to_string()
                 })
                 .collect::<Vec<String>>()
                 .join(" ")
         };
 
-        write!(f, "[{} -> {}, {}]", self.prod.from, to_str, self.lookahead)
+        format!(
+            "[{} -> {}, {}]",
+            g.symbols().symbol(rule.from),
+            to_str,
+            g.symbols().symbol(self.lookahead)
+        )
     }
 }
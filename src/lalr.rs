@@ -0,0 +1,228 @@
+use std::fmt;
+
+use super::{Automaton, Conflict, Grammar, Parser, Table};
+
+/// Where a conflict of the LALR(1) table comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ConflictOrigin {
+    /// One of the merged canonical states already has the conflict, so the
+    /// grammar is not LR(1) either.
+    Inherent,
+    /// Every canonical state is fine on its own; the conflict only appears
+    /// once their lookaheads are merged.
+    Merging,
+}
+
+impl fmt::Display for ConflictOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConflictOrigin::Inherent => write!(f, "inherent to LR(1)"),
+            ConflictOrigin::Merging => write!(f, "introduced by merging"),
+        }
+    }
+}
+
+/// A conflict of the LALR(1) table, with the canonical states behind it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LalrConflict {
+    /// The conflict, in terms of the LALR(1) state.
+    pub conflict: Conflict,
+    pub origin: ConflictOrigin,
+    /// The canonical states merged into the LALR(1) state that have an
+    /// action on the lookahead.
+    pub canonical_states: Vec<usize>,
+}
+
+impl LalrConflict {
+    pub fn to_string(&self, g: &Grammar) -> String {
+        let states: Vec<String> = self.canonical_states.iter().map(|s| s.to_string()).collect();
+        format!(
+            "{} ({}, canonical states {})",
+            self.conflict.to_string(g),
+            self.origin,
+            states.join(", ")
+        )
+    }
+}
+
+/// What merging the canonical LR(1) states with the same core does to the
+/// table: which states were merged and the conflicts of the result.
+#[derive(Debug, Clone)]
+pub struct LalrReport {
+    /// The LALR(1) state of every canonical state.
+    pub state_of: Vec<usize>,
+    /// The canonical states merged into every LALR(1) state.
+    pub merged: Vec<Vec<usize>>,
+    pub table: Table,
+    pub conflicts: Vec<LalrConflict>,
+}
+
+impl LalrReport {
+    /// Merges the states of `automaton`, built from `g`, and labels every
+    /// conflict of the merged table.
+    pub fn new(g: &Grammar, automaton: &Automaton) -> LalrReport {
+        let canonical = Table::new(automaton, g);
+        let (lalr, state_of) = automaton.merge_cores();
+        let table = Table::new(&lalr, g);
+
+        let mut merged = vec![Vec::new(); lalr.states.len()];
+        for (i, &state) in state_of.iter().enumerate() {
+            merged[state].push(i);
+        }
+
+        let mut conflicts = Vec::new();
+        for (state, canonical_states) in merged.iter().enumerate() {
+            for t in g.symbols().terminals() {
+                let actions = table.actions(state, t);
+                if actions.len() < 2 {
+                    continue;
+                }
+
+                let canonical_states: Vec<usize> = canonical_states
+                    .iter()
+                    .cloned()
+                    .filter(|&s| !canonical.actions(s, t).is_empty())
+                    .collect();
+                let origin = if canonical_states.iter().any(|&s| canonical.actions(s, t).len() > 1) {
+                    ConflictOrigin::Inherent
+                } else {
+                    ConflictOrigin::Merging
+                };
+                let items = Conflict::involved_items(lalr.state_items(g, state), t, g);
+
+                conflicts.push(LalrConflict {
+                    conflict: Conflict::new(state, t, &actions, items),
+                    origin,
+                    canonical_states,
+                });
+            }
+        }
+
+        LalrReport {
+            state_of,
+            merged,
+            table,
+            conflicts,
+        }
+    }
+
+    /// The conflicts that the canonical LR(1) table does not have.
+    pub fn merging_conflicts(&self) -> Vec<&LalrConflict> {
+        self.conflicts
+            .iter()
+            .filter(|conflict| conflict.origin == ConflictOrigin::Merging)
+            .collect()
+    }
+
+    pub fn to_string(&self, g: &Grammar) -> String {
+        let mut s = format!(
+            "{} canonical states merged into {} LALR(1) states, {} conflicts",
+            self.state_of.len(),
+            self.merged.len(),
+            self.conflicts.len()
+        );
+        for conflict in &self.conflicts {
+            s.push('\n');
+            s.push_str(&conflict.to_string(g));
+        }
+        s
+    }
+}
+
+impl Parser {
+    /// Diagnoses what an LALR(1) construction would do with the grammar,
+    /// unless the automaton was dropped.
+    pub fn lalr_report(&self) -> Option<LalrReport> {
+        self.automaton()
+            .map(|automaton| LalrReport::new(self.grammar(), automaton))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_util::*;
+
+    // LR(1) but not LALR(1): the states after `a c` and `b c` share a core.
+    fn merging_grammar() -> Grammar {
+        Grammar::from_str(
+            "S",
+            vec!["S", "A", "B"],
+            vec![
+                ("S", vec!["a", "A", "d"]),
+                ("S", vec!["b", "B", "d"]),
+                ("S", vec!["a", "B", "e"]),
+                ("S", vec!["b", "A", "e"]),
+                ("A", vec!["c"]),
+                ("B", vec!["c"]),
+            ],
+        )
+    }
+
+    #[test]
+    fn merging_conflict_test() {
+        let parser = Parser::new(merging_grammar());
+        assert!(parser.is_lr1());
+
+        let report = parser.lalr_report().unwrap();
+        assert_eq!(report.state_of.len(), parser.table().state_count());
+        assert!(report.merged.len() < report.state_of.len());
+        for (state, canonical_states) in report.merged.iter().enumerate() {
+            assert!(canonical_states.iter().all(|&s| report.state_of[s] == state));
+        }
+
+        let symbols = parser.grammar().symbols();
+        let lookaheads: Vec<_> = report.conflicts.iter().map(|c| c.conflict.lookahead).collect();
+        assert_eq!(
+            lookaheads,
+            vec![symbols.terminal_id("d").unwrap(), symbols.terminal_id("e").unwrap()]
+        );
+        for conflict in &report.conflicts {
+            assert_eq!(conflict.origin, ConflictOrigin::Merging);
+            assert_eq!(conflict.conflict.productions, vec![5, 6]);
+            assert_eq!(conflict.canonical_states.len(), 2);
+            assert!(
+                conflict.to_string(parser.grammar()).contains("introduced by merging"),
+                "{}",
+                conflict.to_string(parser.grammar())
+            );
+        }
+        assert_eq!(report.merging_conflicts().len(), 2);
+    }
+
+    #[test]
+    fn inherent_conflict_test() {
+        let parser = Parser::new(sum_grammar());
+
+        let report = parser.lalr_report().unwrap();
+        assert!(!report.conflicts.is_empty());
+        assert!(report.merging_conflicts().is_empty());
+        assert!(report.conflicts.iter().all(|c| c.origin == ConflictOrigin::Inherent));
+    }
+
+    fn cc_grammar() -> Grammar {
+        Grammar::from_str(
+            "S",
+            vec!["S", "C"],
+            vec![("S", vec!["C", "C"]), ("C", vec!["c", "C"]), ("C", vec!["d"])],
+        )
+    }
+
+    #[test]
+    fn lalr_parse_test() {
+        let mut parser = Parser::new(cc_grammar());
+        let report = parser.lalr_report().unwrap();
+        assert!(report.conflicts.is_empty());
+        assert_eq!(parser.table().state_count(), 10);
+        assert_eq!(report.table.state_count(), 7);
+
+        // An LALR(1) grammar gets the same language from the merged table.
+        let lalr = Parser::from_parts(cc_grammar().with_fake_goal(), None, report.table);
+        for case in &["c d d EOF", "d c c d EOF"] {
+            assert!(lalr.parse(lex(case)).is_ok(), "{}", case);
+        }
+
+        parser.drop_automaton();
+        assert!(parser.lalr_report().is_none());
+    }
+}
@@ -5,6 +5,25 @@ mod item;
 mod parser;
 mod tree;
 mod token_like;
+mod bitset;
+mod state;
+mod automaton;
+mod table;
+mod compress;
+mod serialize;
+mod codegen;
+mod conflict;
+mod counterexample;
+mod precedence;
+mod lalr;
+mod glr;
+mod forest;
+mod earley;
+mod cyk;
+mod classify;
+mod error;
+#[cfg(test)]
+mod test_util;
 
 pub use symbol::*;
 pub use production::*;
@@ -13,7 +32,23 @@ pub use item::*;
 pub use parser::*;
 pub use tree::*;
 pub use token_like::*;
+pub use bitset::*;
+pub use state::*;
+pub use automaton::*;
+pub use table::*;
+pub use compress::*;
+pub use codegen::*;
+pub use conflict::*;
+pub use counterexample::*;
+pub use precedence::*;
+pub use lalr::*;
+pub use glr::*;
+pub use forest::*;
+pub use earley::*;
+pub use cyk::*;
+pub use classify::*;
+pub use error::*;
 
-pub const LAMBDA: &'static str = "LAMBDA";
-pub const EOF: &'static str = "EOF";
-pub const FAKE_GOAL: &'static str = "FAKE_GOAL";
+pub const LAMBDA: &str = "LAMBDA";
+pub const EOF: &str = "EOF";
+pub const FAKE_GOAL: &str = "FAKE_GOAL";
@@ -1,470 +1,550 @@
-use std::collections::{HashMap, BTreeSet};
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::mem;
 use std::rc::Rc;
-use std::cell::RefCell;
-use super::{Symbol, Grammar, Production, EOF, Item, NodeId, Tree, TokenLike};
+use super::{SymbolId, SymbolTable, Grammar, EOF, Item, NodeId, Tree, TokenLike, Automaton, Table,
+            ActionEntry, CompressedTable, CompressionReport, Conflict, ConflictError, ConflictKind,
+            ConflictResolver, Counterexample, ParseError};
+use super::error::terminal_names;
 
 //TODO
 //Print Tree should have connected children (see algortihms/bst)
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Action {
     Accept,
-    Reduce(Rc<Production>),
-    Shift(Rc<BTreeSet<Item>>),
+    Reduce(usize),
+    Shift(usize),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum StackEl {
-    Symbol((Symbol, Option<NodeId>)),
-    State(Rc<BTreeSet<Item>>),
+    Symbol((SymbolId, Option<NodeId>)),
+    State(usize),
 }
 
 impl StackEl {
     pub fn is_symbol(&self) -> bool {
-        match self {
-            &StackEl::Symbol(_) => true,
-            _ => false,
-        }
+        matches!(*self, StackEl::Symbol(_))
     }
 
-    pub fn unwrap_symbol(self) -> (Symbol, Option<NodeId>) {
+    pub fn unwrap_symbol(self) -> (SymbolId, Option<NodeId>) {
         if let StackEl::Symbol(symbol) = self {
-            return symbol;
+            symbol
         } else {
             panic!("Unexpected unwrap_symbol a non symbol StackEl")
         }
     }
 }
 
+// Boxed resolvers have no Debug of their own.
+struct Resolver(Box<dyn ConflictResolver + Send + Sync>);
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ConflictResolver")
+    }
+}
+
+/// A compiled parser. It is immutable while parsing: every parse runs in
+/// its own `ParseSession`, so a parser can be shared between threads (e.g.
+/// behind an `Arc`) and parse any number of inputs at once.
 #[derive(Debug)]
 pub struct Parser {
     grammar: Grammar,
-    cc: BTreeSet<Rc<BTreeSet<Item>>>,
-    goto_map: HashMap<(Rc<BTreeSet<Item>>, Symbol), BTreeSet<Rc<BTreeSet<Item>>>>,
-    action: HashMap<(Rc<BTreeSet<Item>>, Symbol), BTreeSet<Action>>,
+    // The canonical collection, kept for diagnostics only.
+    automaton: Option<Automaton>,
+    table: Table,
+    // Used instead of `table` for parsing once `compress` is called.
+    compressed: Option<CompressedTable>,
+    // Conflicts resolved by default because the grammar expects conflicts.
+    resolved: Vec<Conflict>,
+    // Picks an action when a conflicting cell is reached while parsing.
+    resolver: Option<Resolver>,
+    // Empty unless `use_default_reductions` is called.
+    default_reductions: Vec<Option<usize>>,
+}
 
-    index_to_cc: Vec<Rc<BTreeSet<Item>>>,
-    cc_to_index: HashMap<Rc<BTreeSet<Item>>, usize>,
+/// What `ParseSession::feed` made of a token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Feed {
+    /// The token was shifted and the parse goes on.
+    NeedMore,
+    /// The input was accepted; `finish` returns the tree.
+    Accepted,
+    /// The input was rejected; the session stays in this state.
+    Error(ParseError),
+}
 
-    stack: RefCell<Vec<StackEl>>,
+/// How far a token sequence is from being a sentence of the grammar; see
+/// `Parser::completeness`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Completeness {
+    /// EOF would be accepted next.
+    Complete,
+    /// A valid prefix that needs more tokens.
+    Incomplete,
+    /// The token at this index is rejected.
+    Invalid(usize),
 }
 
-impl Parser {
-    pub fn new(g: Grammar) -> Parser {
-        let mut p = Parser {
-            grammar: g.with_fake_goal(),
-            cc: BTreeSet::new(),
-            goto_map: HashMap::new(),
-            action: HashMap::new(),
+/// The state of a single parse, kept apart from the parser it runs on.
+///
+/// Tokens are either pulled from an iterator with `parse`, or pushed one at
+/// a time with `feed`, with no limit on the time between two tokens.
+pub struct ParseSession<'p> {
+    parser: &'p Parser,
+    // Empty until the first token is fed. Shared with the checkpoints taken
+    // since it last changed.
+    stack: Rc<Vec<StackEl>>,
+    tree: Tree,
+    // Index of the next token.
+    fed: usize,
+    // Set once the input is accepted or rejected.
+    outcome: Option<Feed>,
+    // Bumped when the tree is handed out or a new parse starts, which
+    // invalidates the checkpoints taken before.
+    epoch: usize,
+    // Whether `finish` handed out the tree.
+    finished: bool,
+}
 
-            index_to_cc: Vec::new(),
-            cc_to_index: HashMap::new(),
+/// A saved state of a `ParseSession`, to go back to with `restore`.
+///
+/// Taking one is cheap: the stack is only copied when the session changes
+/// it afterwards, and the tree is rolled back by dropping the nodes added
+/// since.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    stack: Rc<Vec<StackEl>>,
+    tree_len: usize,
+    root: Option<NodeId>,
+    fed: usize,
+    outcome: Option<Feed>,
+    epoch: usize,
+}
 
-            stack: RefCell::new(Vec::new()),
-        };
+impl Parser {
+    pub fn new(g: Grammar) -> Parser {
+        let grammar = g.with_fake_goal();
+        let automaton = Automaton::new(&grammar);
+        let table = Table::new(&automaton, &grammar);
+
+        let mut parser = Parser::from_parts(grammar, Some(automaton), table);
+        if parser.grammar.expected_conflicts().is_some() {
+            parser.resolved = parser.conflicts();
+            for conflict in &parser.resolved {
+                let action = conflict.default_resolution();
+                parser.table.set_actions(conflict.state, conflict.lookahead, &[action]);
+            }
+        }
+        parser
+    }
 
-        p.build_cc();
+    /// Like `new`, but fails if the action table has conflicts, or if the
+    /// grammar expects conflicts and their number differs.
+    pub fn try_new(g: Grammar) -> Result<Parser, ConflictError> {
+        let parser = Parser::new(g);
+        let conflicts = parser.conflicts();
+        if !conflicts.is_empty() {
+            Err(ConflictError::new(conflicts, &parser))
+        } else if !parser.expectation_met() {
+            Err(ConflictError::new(parser.resolved.clone(), &parser))
+        } else {
+            Ok(parser)
+        }
+    }
 
-        p
+    /// Assembles a parser from a grammar that already has the fake goal and
+    /// its compiled table.
+    pub(crate) fn from_parts(grammar: Grammar, automaton: Option<Automaton>, table: Table) -> Parser {
+        Parser {
+            grammar,
+            automaton,
+            table,
+            compressed: None,
+            resolved: Vec::new(),
+            resolver: None,
+            default_reductions: Vec::new(),
+        }
     }
 
-    pub fn insert_cc(&mut self, cc_i: Rc<BTreeSet<Item>>) {
-        assert!(!self.cc_to_index.contains_key(&cc_i));
-        let index = self.index_to_cc.len();
+    pub fn grammar(&self) -> &Grammar {
+        &self.grammar
+    }
 
-        self.index_to_cc.push(cc_i.clone());
-        self.cc_to_index.insert(cc_i.clone(), index);
+    pub fn table(&self) -> &Table {
+        &self.table
     }
 
-    pub fn closure(&self, items: &BTreeSet<Item>) -> Rc<BTreeSet<Item>> {
-        let mut new_items = items.clone();
-        let mut items = BTreeSet::new();
+    /// The canonical collection, unless it was dropped with `drop_automaton`.
+    pub fn automaton(&self) -> Option<&Automaton> {
+        self.automaton.as_ref()
+    }
 
-        while !new_items.is_subset(&items) {
-            items = items.union(&new_items).cloned().collect();
-            new_items.clear();
+    /// Frees the item sets; parsing only needs the compiled table.
+    pub fn drop_automaton(&mut self) {
+        self.automaton = None;
+    }
 
-            let filtered_items = items
-                .iter()
-                .filter(|item| item.stacktop().is_some())
-                .filter(|item| item.stacktop().unwrap().is_non_terminal())
-                .filter(|item| {
-                    self.grammar.get_prods(item.stacktop().unwrap()).is_some()
-                });
+    /// Parses from a compressed copy of the table from now on.
+    ///
+    /// Rows with a default reduction reduce instead of failing on unexpected
+    /// tokens, so errors may be reported a few reductions later (but still
+    /// before the offending token is shifted).
+    pub fn compress(&mut self) -> CompressionReport {
+        let compressed = CompressedTable::new(&self.table);
+        let report = compressed.report();
+        self.compressed = Some(compressed);
+        report
+    }
 
+    pub fn compressed_table(&self) -> Option<&CompressedTable> {
+        self.compressed.as_ref()
+    }
 
-            for item in filtered_items {
-                for prod in self.grammar.get_prods(item.stacktop().unwrap()).unwrap() {
-                    let first = self.grammar.first_of(&item.after_stacktop_and_lookahead());
-                    if first == None {
-                        continue;
-                    }
+    /// Reduces without reading the lookahead in states where reducing is the
+    /// only possible action, so a complete construct is reduced before the
+    /// next token is requested. Returns the number of such states.
+    ///
+    /// Like with `compress`, a syntax error may be reported a few reductions
+    /// later than without default reductions.
+    pub fn use_default_reductions(&mut self) -> usize {
+        self.default_reductions = self.table.default_reductions();
+        self.default_reductions.iter().filter(|prod| prod.is_some()).count()
+    }
 
-                    for b in first.unwrap() {
-                        let item = Item::from_production(prod.clone(), b.clone());
-                        new_items.insert(item);
-                    }
-                }
-            }
+    pub fn uses_default_reductions(&self) -> bool {
+        !self.default_reductions.is_empty()
+    }
 
-            //println!("CLOSURE>>>items     {:?}", Item::set_to_string(&items));
-            //println!("CLOSURE>>>new items {:?}\n",
-            //Item::set_to_string(&new_items));
-        }
+    /// The reduction done in `state` regardless of the lookahead, if default
+    /// reductions are in use.
+    pub fn default_reduction(&self, state: usize) -> Option<usize> {
+        self.default_reductions.get(state).cloned().unwrap_or(None)
+    }
 
-        Rc::new(items)
+    /// Resolves the conflicts left in the table at parse time, instead of
+    /// failing when one is reached. The resolver may be called from several
+    /// threads at once, like the parser.
+    pub fn set_conflict_resolver<R>(&mut self, resolver: R)
+    where
+        R: ConflictResolver + Send + Sync + 'static,
+    {
+        self.resolver = Some(Resolver(Box::new(resolver)));
     }
 
-    pub fn goto(&self, items: &BTreeSet<Item>, x: &Symbol) -> Option<Rc<BTreeSet<Item>>> {
-        let next: BTreeSet<Item> = items
-            .iter()
-            .filter(|&item| item.stacktop().is_some())
-            .filter(|&item| item.stacktop().unwrap() == x)
-            .map(|item| item.clone_with_next_stacktop())
-            .collect();
+    /// The full item set of the state `i`, if the automaton is available.
+    pub fn state_items(&self, i: usize) -> Option<BTreeSet<Item>> {
+        self.automaton
+            .as_ref()
+            .map(|automaton| automaton.state_items(&self.grammar, i))
+    }
 
-        if next.is_empty() {
-            return None;
-        } else {
-            //println!("GOTO>>>next {:?}\n", Item::set_to_string(&next));
-            Some(self.closure(&next))
+    fn action_entry(&self, state: usize, terminal: SymbolId) -> ActionEntry<'_> {
+        match self.compressed {
+            Some(ref compressed) => compressed.action(state, terminal),
+            None => self.table.action(state, terminal),
         }
     }
 
-    fn build_cc(&mut self) {
-        let cc0 = {
-            let item = Item::from_production(self.grammar.productions[0].clone(), Symbol::eof());
-            let mut set = BTreeSet::new();
-            set.insert(item);
-            self.closure(&set)
+    fn get_single_goto(&self, state: usize, non_terminal: SymbolId) -> Result<usize, String> {
+        let index = self.grammar.symbols().non_terminal_index(non_terminal);
+        let next = match self.compressed {
+            Some(ref compressed) => compressed.goto(state, index),
+            None => self.table.goto(state, index),
         };
+        next.ok_or_else(
+            || "Next state is empty".to_string(),
+        )
+    }
 
-        let mut cc = BTreeSet::new();
-        let mut new_cc = {
-            let mut set = BTreeSet::new();
-            set.insert(cc0.clone());
-            set
-        };
-
-        self.insert_cc(cc0);
-
-        let mut done = BTreeSet::new();
-
-        while !new_cc.is_empty() {
-            cc = cc.union(&new_cc).cloned().collect();
-            new_cc.clear();
-
-            //println!("\nBUILD_CC>>>CC \n{}", Item::set_of_sets_to_string(&cc));
-            for cc_i in &cc {
-                if done.contains(cc_i) {
-                    continue;
-                }
-                for item in cc_i.iter() {
-                    if item.is_complete() {
-                        let entry = self.action
-                            .entry((cc_i.clone(), item.lookahead.clone()))
-                            .or_insert(BTreeSet::new());
-
-                        if item.is_terminator() {
-                            entry.insert(Action::Accept);
-                        } else {
-                            entry.insert(Action::Reduce(item.prod.clone()));
-                        }
-                        continue;
-                    }
-
-                    let stacktop = item.stacktop().unwrap();
-                    let next = self.goto(cc_i, &stacktop);
-                    if next == None {
-                        println!("NONE NONE NONE");
-                        continue;
-                    }
-                    let next = next.unwrap();
-                    if !cc.contains(&next) {
-                        let is_new = new_cc.insert(next.clone());
-                        if is_new {
-                            self.insert_cc(next.clone());
-                        }
-                    }
-
-                    if stacktop.is_terminal() {
-                        let entry = self.action
-                            .entry((cc_i.clone(), stacktop.clone()))
-                            .or_insert(BTreeSet::new());
-
-                        entry.insert(Action::Shift(next.clone()));
-
-                    } else {
-                        let entry = self.goto_map
-                            .entry((cc_i.clone(), stacktop.clone()))
-                            .or_insert(BTreeSet::new());
-                        entry.insert(next.clone());
+    // Whether `terminal` is shifted or accepted from the states `states`,
+    // after the reductions it leads to. `seen` cuts reduction cycles going
+    // through conflicts.
+    fn reaches_shift(&self, mut states: Vec<usize>, terminal: SymbolId, seen: &mut HashSet<Vec<usize>>) -> bool {
+        loop {
+            let state = match states.last() {
+                Some(&state) => state,
+                None => return false,
+            };
+            let prod = match self.default_reduction(state) {
+                Some(prod) => prod,
+                None => match self.action_entry(state, terminal) {
+                    ActionEntry::Error => return false,
+                    ActionEntry::Action(Action::Reduce(prod)) => prod,
+                    ActionEntry::Action(_) => return true,
+                    ActionEntry::Conflict(actions) => {
+                        return seen.insert(states.clone()) && actions.iter().any(|&action| match action {
+                            Action::Reduce(prod) => {
+                                let mut states = states.clone();
+                                self.reduce_states(&mut states, prod) &&
+                                    self.reaches_shift(states, terminal, seen)
+                            }
+                            _ => true,
+                        });
                     }
-
-                }
-
-                done.insert(cc_i.clone());
+                },
+            };
+            if !self.reduce_states(&mut states, prod) {
+                return false;
             }
         }
-
-        self.cc = cc;
-    }
-
-    fn get_single_action<'a, 'b>(
-        &'a self,
-        key: &'b (Rc<BTreeSet<Item>>, Symbol),
-    ) -> Result<&'a Action, String> {
-
-        let &(ref s, ref x) = key;
-        let action = self.action.get(key);
-        action
-            .ok_or(format!(
-                "Next action is empty.\nAction {}, {}, {} -> {:?}\nStack {}",
-                self.cc_to_index.get(s).unwrap(),
-                Item::set_to_string(s),
-                x,
-                action,
-                self.stack_to_string()
-            ))
-            .and_then(|actions| if actions.len() != 1 {
-                Err(format!("Found conflicts in the Action table"))
-            } else {
-                Ok(actions)
-            })
-            .map(|actions| {
-                actions.iter().take(1).collect::<Vec<&Action>>()[0]
-            })
     }
 
-    fn get_single_goto<'a, 'b>(
-        &'a self,
-        key: &'b (Rc<BTreeSet<Item>>, Symbol),
-    ) -> Result<&'a Rc<BTreeSet<Item>>, String> {
-
-        self.goto_map
-            .get(key)
-            .ok_or(format!("Next state is empty"))
-            .and_then(|states| if states.len() != 1 {
-                Err(format!("Something really bad happened"))
-            } else {
-                Ok(states)
-            })
-            .map(|states| {
-                states.iter().take(1).collect::<Vec<&Rc<BTreeSet<Item>>>>()[0]
-            })
+    // Replaces the states of the right hand side of `prod` with the goto
+    // state of its left hand side.
+    fn reduce_states(&self, states: &mut Vec<usize>, prod: usize) -> bool {
+        let rule = &self.grammar.rules()[prod];
+        if rule.to.len() >= states.len() {
+            return false;
+        }
+        let len = states.len() - rule.to.len();
+        states.truncate(len);
+        match self.get_single_goto(states[len - 1], rule.from) {
+            Ok(next) => {
+                states.push(next);
+                true
+            }
+            Err(_) => false,
+        }
     }
 
-    fn get_stacktop_state(&self) -> Result<Rc<BTreeSet<Item>>, String> {
-        self.stack
-            .borrow()
-            .last()
-            .ok_or(format!("Empty stack"))
-            .and_then(|el| match el {
-                &StackEl::State(ref s) => Ok(s.clone()),
-                _ => Err(format!("Attempting to read an invalid state from stack")),
-            })
+    fn terminal_of(&self, word: &dyn TokenLike) -> Result<SymbolId, String> {
+        self.grammar.symbols().terminal_id(word.kind()).ok_or_else(|| {
+            format!("Unexpected token kind {}", word.kind())
+        })
     }
 
     pub fn is_lr1(&self) -> bool {
-        self.action.iter().all(|(_, actions)| actions.len() <= 1)
+        !self.table.has_conflicts()
     }
 
-    pub fn parse<I>(&self, mut tokens: I) -> Result<Tree, String>
-    where
-        I: Iterator<Item = Box<TokenLike>>,
-    {
-        use Action::*;
+    fn conflict(&self, state: usize, terminal: SymbolId, actions: &[Action]) -> Conflict {
+        let items = self.state_items(state)
+            .map(|items| Conflict::involved_items(items, terminal, &self.grammar))
+            .unwrap_or_default();
+        Conflict::new(state, terminal, actions, items)
+    }
 
-        let mut tree = Tree::new();
+    /// Conflicts resolved by default because the grammar expects conflicts.
+    pub fn resolved_conflicts(&self) -> &[Conflict] {
+        &self.resolved
+    }
 
-        {
-            let mut stack = self.stack.borrow_mut();
-            *stack = vec![
-                StackEl::Symbol((Symbol::eof(), None)),
-                StackEl::State(self.index_to_cc.get(0).unwrap().clone()),
-            ];
-        }
+    // For parsers loaded with their table already resolved.
+    pub(crate) fn set_resolved_conflicts(&mut self, resolved: Vec<Conflict>) {
+        self.resolved = resolved;
+    }
 
+    fn resolved_count(&self, kind: ConflictKind) -> usize {
+        self.resolved.iter().filter(|conflict| conflict.kind == kind).count()
+    }
 
-        let mut word = {
-            let word = tokens.next();
-            if word.is_none() {
-                return Ok((tree));
+    fn expectation_met(&self) -> bool {
+        match self.grammar.expected_conflicts() {
+            Some((shift_reduce, reduce_reduce)) => {
+                self.resolved_count(ConflictKind::ShiftReduce) == shift_reduce &&
+                    self.resolved_count(ConflictKind::ReduceReduce) == reduce_reduce
             }
-
-            word.unwrap()
-        };
-
-        if word.kind() == EOF {
-            return Ok(tree);
+            None => true,
         }
+    }
 
-        loop {
-            let state = self.get_stacktop_state()?;
-            let action = self.get_single_action(
-                &(state.clone(), Symbol::new_t(word.kind())),
-            )?;
-
-            match action {
-                &Reduce(ref prod) => {
-                    let new_root = tree.new_node(prod.from.clone());
-                    tree.set_root(new_root);
-
+    /// One warning per conflict resolved by default, plus one if their
+    /// number is not the expected one.
+    pub fn warnings(&self) -> Vec<String> {
+        let symbols = self.grammar.symbols();
+        let mut warnings: Vec<String> = self.resolved
+            .iter()
+            .map(|conflict| {
+                let action = match conflict.default_resolution() {
+                    Action::Accept => "accept".to_string(),
+                    Action::Shift(_) => "shift".to_string(),
+                    Action::Reduce(prod) => format!("reduce {}", self.grammar.productions[prod]),
+                };
+                format!(
+                    "{} conflict in state {} on {} resolved as {}",
+                    conflict.kind,
+                    conflict.state,
+                    symbols.symbol(conflict.lookahead).as_str(),
+                    action
+                )
+            })
+            .collect();
 
-                    let to_pop = prod.to.len() * 2;
-                    let stack_len = self.stack.borrow().len();
+        if !self.expectation_met() {
+            let (shift_reduce, reduce_reduce) = self.grammar.expected_conflicts().unwrap();
+            warnings.push(format!(
+                "expected {} shift/reduce and {} reduce/reduce conflicts, found {} and {}",
+                shift_reduce,
+                reduce_reduce,
+                self.resolved_count(ConflictKind::ShiftReduce),
+                self.resolved_count(ConflictKind::ReduceReduce)
+            ));
+        }
+        warnings
+    }
 
-                    if to_pop > stack_len {
-                        return Err(format!("Reduce Error: empty stack"));
-                    }
+    /// Explains how the parser gets to `conflict`, unless the automaton was
+    /// dropped.
+    pub fn counterexample(&self, conflict: &Conflict) -> Option<Counterexample> {
+        self.automaton
+            .as_ref()
+            .map(|automaton| Counterexample::new(&self.grammar, automaton, conflict))
+    }
 
-                    let popped = self.stack.borrow_mut().split_off(stack_len - to_pop);
-                    popped
-                        .into_iter()
-                        .filter(|el| el.is_symbol())
-                        .map(|el| el.unwrap_symbol())
-                        .map(|(_, child_id)| child_id)
-                        .map(|child_id| {
-                            tree.append(new_root, child_id.expect("Unexpected EOF"))
-                        })
-                        .collect::<Vec<()>>();
-
-
-                    let state = self.get_stacktop_state()?;
-                    let next = self.get_single_goto(&(state, prod.from.clone()))?;
-                    self.stack.borrow_mut().push(StackEl::Symbol(
-                        (prod.from.clone(), Some(new_root)),
-                    ));
-                    self.stack.borrow_mut().push(StackEl::State(next.clone()));
+    /// Every conflict of the action table, by state and terminal.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+        for state in 0..self.table.state_count() {
+            for t in self.grammar.symbols().terminals() {
+                if let ActionEntry::Conflict(actions) = self.table.action(state, t) {
+                    conflicts.push(self.conflict(state, t, actions));
                 }
+            }
+        }
+        conflicts
+    }
 
-                &Shift(ref next_state) => {
-                    let mut stack = self.stack.borrow_mut();
-                    let new_symbol = Symbol::new_t(word.kind());
-                    let node_id = tree.new_node(word);
-                    stack.push(StackEl::Symbol((new_symbol, Some(node_id))));
-                    stack.push(StackEl::State(next_state.clone()));
+    /// A new parse on this parser.
+    pub fn session(&self) -> ParseSession<'_> {
+        ParseSession {
+            parser: self,
+            stack: Rc::new(Vec::new()),
+            tree: Tree::new(),
+            fed: 0,
+            outcome: None,
+            epoch: 0,
+            finished: false,
+        }
+    }
 
-                    word = tokens.next().ok_or(
-                        format!("Unexpected end of token stream"),
-                    )?;
-                }
+    /// Parses the tokens, up to EOF, in a session of its own.
+    pub fn parse<I>(&self, tokens: I) -> Result<Tree, ParseError>
+    where
+        I: Iterator<Item = Box<dyn TokenLike>>,
+    {
+        self.session().parse(tokens)
+    }
 
-                &Accept => {
-                    return Ok((tree));
-                }
+    /// Whether the tokens, given without EOF, make a complete input, the
+    /// start of one, or neither. Meant for REPLs deciding whether to ask for
+    /// another line.
+    pub fn completeness<I>(&self, tokens: I) -> Completeness
+    where
+        I: Iterator<Item = Box<dyn TokenLike>>,
+    {
+        let mut session = self.session();
+        for (i, word) in tokens.enumerate() {
+            // Asked rather than fed, since `feed` takes EOF alone as an
+            // empty input whatever the grammar.
+            if word.kind() == EOF {
+                return if session.accepts(SymbolTable::eof()) {
+                    Completeness::Complete
+                } else {
+                    Completeness::Invalid(i)
+                };
+            }
+            match session.feed(word) {
+                Feed::NeedMore => {}
+                Feed::Accepted => return Completeness::Complete,
+                Feed::Error(_) => return Completeness::Invalid(i),
             }
         }
-    }
-
 
-    pub fn stack_to_string(&self) -> String {
-        self.stack
-            .borrow()
-            .iter()
-            .map(|el| match el {
-                &StackEl::Symbol((ref s, ref node_id)) => format!("{} {:?}", s, node_id),
-                &StackEl::State(ref s) => self.cc_to_index.get(s).unwrap().to_string(),
-            })
-            .collect::<Vec<String>>()
-            .join(", ")
+        if session.accepts(SymbolTable::eof()) {
+            Completeness::Complete
+        } else {
+            Completeness::Incomplete
+        }
     }
 
     pub fn print_cc(&self) {
         println!("CC");
         println!("======");
 
-        for (i, cc_i) in self.index_to_cc.iter().enumerate() {
-            println!("{:<4} {}", i, Item::set_to_string(cc_i));
+        for i in 0..self.table.state_count() {
+            match self.state_items(i) {
+                Some(items) => {
+                    println!("{:<4} {}", i, Item::set_to_string(&items, &self.grammar))
+                }
+                None => println!("{:<4} (item sets dropped)", i),
+            }
         }
         println!("\n");
     }
 
 
     pub fn print_tables(&self) {
-        println!("");
+        let symbols = self.grammar.symbols();
+
+        println!();
         println!("ACTION");
         println!("======");
 
-        for (&(ref cc_i, ref symbol), action) in &self.action {
-            let i = self.cc_to_index.get(cc_i).unwrap();
-            let a = self.set_of_actions_to_string(&action);
-            println!("{:<4} {:<4} -> {}", i, symbol, a);
+        for i in 0..self.table.state_count() {
+            for t in symbols.terminals() {
+                let actions = self.table.actions(i, t);
+                if !actions.is_empty() {
+                    let a = self.set_of_actions_to_string(&actions);
+                    println!("{:<4} {:<4} -> {}", i, symbols.symbol(t), a);
+                }
+            }
         }
 
-        println!("");
+        println!();
         println!("GOTO");
         println!("======");
 
-        for (&(ref cc_i, ref symbol), next) in &self.goto_map {
-            let i = self.cc_to_index.get(cc_i).unwrap();
-            let j = next.iter()
-                .map(|next| self.cc_to_index.get(next).unwrap())
-                .map(|j| j.to_string())
-                .collect::<Vec<String>>()
-                .join(", ");
-            println!("{:<4} {:<4} -> {}", i, symbol, j);
+        for i in 0..self.table.state_count() {
+            for nt in symbols.non_terminals() {
+                if let Some(j) = self.table.goto(i, symbols.non_terminal_index(nt)) {
+                    println!("{:<4} {:<4} -> {}", i, symbols.symbol(nt), j);
+                }
+            }
         }
     }
 
     pub fn action_to_string(&self, action: &Action) -> String {
-        match action {
-            &Action::Accept => "Accept".to_string(),
-            &Action::Reduce(ref prod) => format!("{}", prod),
-            &Action::Shift(ref cc_i) => {
-                format!(
-                    "Shift({})",
-                    self.cc_to_index.get(cc_i).expect(
-                        "action_to_string: bad shift",
-                    )
-                )
-            }
+        match *action {
+            Action::Accept => "Accept".to_string(),
+            Action::Reduce(prod) => format!("{}", self.grammar.productions[prod]),
+            Action::Shift(i) => format!("Shift({})", i),
         }
     }
 
-    pub fn set_of_actions_to_string(&self, action: &BTreeSet<Action>) -> String {
+    pub fn set_of_actions_to_string(&self, action: &[Action]) -> String {
         action
             .iter()
-            .map(|action| self.action_to_string(&action))
+            .map(|action| self.action_to_string(action))
             .collect::<Vec<String>>()
             .join(", ")
     }
 
 
     pub fn pretty_print_tables(&self) {
-        println!("");
+        let symbols = self.grammar.symbols();
+
+        println!();
         println!("ACTION");
         println!("======");
         let mut rows: Vec<Vec<String>> = vec![];
 
-        let mut first_row = vec!["".to_string(), EOF.to_string()];
-        first_row.append(&mut self.grammar
+        let mut first_row = vec!["".to_string()];
+        first_row.append(&mut symbols
             .terminals()
-            .iter()
-            .map(|s| s.to_string())
+            .into_iter()
+            .map(|t| symbols.symbol(t).to_string())
             .cloned()
             .collect());
 
         rows.push(first_row);
 
-
-        let mut terminals = vec![EOF.to_string()];
-        terminals.append(&mut self.grammar
-            .terminals()
-            .iter()
-            .map(|s| s.to_string())
-            .cloned()
-            .collect());
-
-        for (i, cc_i) in self.index_to_cc.iter().enumerate() {
+        for i in 0..self.table.state_count() {
             let mut row = vec![i.to_string()];
-            for t in &terminals {
-                let action = self.action.get(&(cc_i.clone(), Symbol::new_t(t)));
-                if action == None {
-                    row.push("".to_string());
-                } else {
-                    row.push(self.set_of_actions_to_string(action.unwrap()));
-                }
+            for t in symbols.terminals() {
+                row.push(self.set_of_actions_to_string(&self.table.actions(i, t)));
             }
 
             rows.push(row);
@@ -479,32 +559,30 @@ impl Parser {
                     print!("{:<30}", cell);
                 }
             }
-            println!("");
+            println!();
         }
 
-        println!("");
+        println!();
         println!("GOTO");
         println!("====");
         let mut rows: Vec<Vec<String>> = vec![];
 
         let mut first_row = vec!["".to_string()];
-        first_row.append(&mut self.grammar
+        first_row.append(&mut symbols
             .non_terminals()
-            .iter()
-            .map(|s| s.to_string())
+            .into_iter()
+            .map(|nt| symbols.symbol(nt).to_string())
             .cloned()
             .collect());
 
         rows.push(first_row);
 
-        for (i, cc_i) in self.cc.iter().enumerate() {
+        for i in 0..self.table.state_count() {
             let mut row = vec![i.to_string()];
-            for nt in &self.grammar.non_terminals() {
-                let next = self.goto_map.get(&(cc_i.clone(), nt.clone()));
-                if next == None {
-                    row.push("".to_string());
-                } else {
-                    row.push(self.cc_to_index.get(cc_i).unwrap().to_string());
+            for nt in symbols.non_terminals() {
+                match self.table.goto(i, symbols.non_terminal_index(nt)) {
+                    None => row.push("".to_string()),
+                    Some(next) => row.push(next.to_string()),
                 }
             }
             rows.push(row);
@@ -518,28 +596,340 @@ impl Parser {
                     print!("{:<10}", cell);
                 }
             }
-            println!("");
+            println!();
+        }
+    }
+}
+
+impl<'p> ParseSession<'p> {
+    pub fn parser(&self) -> &'p Parser {
+        self.parser
+    }
+
+    /// The stack as left by the last parse.
+    pub fn stack(&self) -> &[StackEl] {
+        &self.stack
+    }
+
+    /// Parses the tokens, up to EOF. The session can be reused for another
+    /// parse afterwards.
+    pub fn parse<I>(&mut self, tokens: I) -> Result<Tree, ParseError>
+    where
+        I: Iterator<Item = Box<dyn TokenLike>>,
+    {
+        self.stack = Rc::new(Vec::new());
+        self.tree = Tree::new();
+        self.fed = 0;
+        self.outcome = None;
+        self.epoch += 1;
+        self.finished = false;
+
+        for word in tokens {
+            match self.feed(word) {
+                Feed::NeedMore => {}
+                Feed::Accepted => break,
+                Feed::Error(err) => return Err(err),
+            }
+        }
+        self.finish()
+    }
+
+    /// Pushes the next token, running every action it leads to. Once the
+    /// input is accepted or rejected, further tokens get the same answer.
+    pub fn feed(&mut self, word: Box<dyn TokenLike>) -> Feed {
+        if let Some(ref outcome) = self.outcome {
+            return outcome.clone();
+        }
+
+        let feed = match self.step(word) {
+            Ok(feed) => feed,
+            Err(err) => Feed::Error(err),
+        };
+        self.fed += 1;
+        if feed != Feed::NeedMore {
+            self.outcome = Some(feed.clone());
+        }
+        feed
+    }
+
+    /// Ends the input, returning the tree if it was accepted. Nothing fed
+    /// at all gives an empty tree, like an input made of EOF alone.
+    ///
+    /// The tree is handed out only once, and the checkpoints taken before
+    /// can no longer be restored.
+    pub fn finish(&mut self) -> Result<Tree, ParseError> {
+        match self.outcome {
+            Some(Feed::Accepted) if self.finished => {
+                Err(ParseError::Internal("The tree was already returned by finish".to_string()))
+            }
+            Some(Feed::Accepted) => {
+                self.finished = true;
+                self.epoch += 1;
+                Ok(mem::replace(&mut self.tree, Tree::new()))
+            }
+            Some(Feed::Error(ref err)) => Err(err.clone()),
+            _ if self.stack.is_empty() => Ok(Tree::new()),
+            _ => Err(ParseError::UnexpectedEnd {
+                state: self.state(),
+                expected: self.expected(),
+            }),
+        }
+    }
+
+    /// The state on top of the stack, 0 before the first token is fed.
+    pub fn state(&self) -> usize {
+        self.get_stacktop_state().unwrap_or(0)
+    }
+
+    /// Whether the action table takes `terminal` next: it is shifted or
+    /// accepted, possibly after some reductions. With conflicts left in the
+    /// table, one of the actions getting there is enough. EOF right away is
+    /// only accepted if the grammar derives the empty string, even though
+    /// `feed` takes it as an empty input.
+    pub fn accepts(&self, terminal: SymbolId) -> bool {
+        if self.outcome.is_some() {
+            return false;
+        }
+
+        let states: Vec<usize> = self.stack
+            .iter()
+            .filter_map(|el| match *el {
+                StackEl::State(s) => Some(s),
+                StackEl::Symbol(_) => None,
+            })
+            .collect();
+        let states = if states.is_empty() { vec![0] } else { states };
+        self.parser.reaches_shift(states, terminal, &mut HashSet::new())
+    }
+
+    /// Every terminal `accepts`, by id.
+    pub fn acceptable(&self) -> Vec<SymbolId> {
+        self.parser
+            .grammar
+            .symbols()
+            .terminals()
+            .into_iter()
+            .filter(|&t| self.accepts(t))
+            .collect()
+    }
+
+    // The names of the acceptable terminals.
+    fn expected(&self) -> Vec<String> {
+        terminal_names(self.parser.grammar.symbols(), self.acceptable())
+    }
+
+    fn unexpected(&self, word: &dyn TokenLike) -> ParseError {
+        ParseError::UnexpectedToken {
+            index: self.fed,
+            kind: word.kind().clone(),
+            lexeme: word.lexeme().clone(),
+            state: self.state(),
+            expected: self.expected(),
+        }
+    }
+
+    /// Saves the current state of the parse.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            stack: self.stack.clone(),
+            tree_len: self.tree.len(),
+            root: self.tree.root(),
+            fed: self.fed,
+            outcome: self.outcome.clone(),
+            epoch: self.epoch,
+        }
+    }
+
+    /// Goes back to the state saved in `checkpoint`, undoing every token
+    /// fed since. The checkpoint must come from this session, and restoring
+    /// it invalidates the checkpoints taken after it.
+    ///
+    /// Fails if the tree was handed out by `finish`, or a new parse started,
+    /// since the checkpoint was taken.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) -> Result<(), String> {
+        if checkpoint.epoch != self.epoch || checkpoint.tree_len > self.tree.len() {
+            return Err("Checkpoint no longer valid".to_string());
+        }
+        self.stack = checkpoint.stack.clone();
+        self.tree.truncate(checkpoint.tree_len);
+        if let Some(root) = checkpoint.root {
+            self.tree.set_root(root);
+        }
+        self.fed = checkpoint.fed;
+        self.outcome = checkpoint.outcome.clone();
+        Ok(())
+    }
+
+    /// Feeds the tokens, going back to where the session was if one of them
+    /// is rejected. Returns what became of the last token fed.
+    pub fn speculate<I>(&mut self, tokens: I) -> Feed
+    where
+        I: IntoIterator<Item = Box<dyn TokenLike>>,
+    {
+        let checkpoint = self.checkpoint();
+        let mut feed = Feed::NeedMore;
+        for word in tokens {
+            feed = self.feed(word);
+            if feed != Feed::NeedMore {
+                break;
+            }
+        }
+        if let Feed::Error(_) = feed {
+            self.restore(&checkpoint).expect("Unexpected invalid checkpoint");
+        }
+        feed
+    }
+
+    fn step(&mut self, word: Box<dyn TokenLike>) -> Result<Feed, ParseError> {
+        use Action::*;
+
+        let parser = self.parser;
+        if self.stack.is_empty() {
+            self.stack = Rc::new(vec![StackEl::Symbol((SymbolTable::eof(), None)), StackEl::State(0)]);
+            if word.kind() == EOF {
+                return Ok(Feed::Accepted);
+            }
+        }
+
+        let terminal = match parser.terminal_of(&*word) {
+            Ok(terminal) => terminal,
+            Err(_) => return Err(self.unexpected(&*word)),
+        };
+        let mut word = Some(word);
+
+        loop {
+            let state = self.get_stacktop_state().map_err(ParseError::Internal)?;
+
+            let action = match parser.default_reduction(state) {
+                Some(prod) => Reduce(prod),
+                // Shifted already: the next token is needed.
+                None if word.is_none() => return Ok(Feed::NeedMore),
+                None => self.get_single_action(state, terminal, &**word.as_ref().unwrap())?,
+            };
+
+            match action {
+                Reduce(prod) => self.reduce(prod).map_err(ParseError::Internal)?,
+
+                Shift(next_state) => {
+                    let node_id = self.tree.new_node(word.take().unwrap());
+                    let stack = Rc::make_mut(&mut self.stack);
+                    stack.push(StackEl::Symbol((terminal, Some(node_id))));
+                    stack.push(StackEl::State(next_state));
+                }
+
+                Accept => return Ok(Feed::Accepted),
+            }
+        }
+    }
+
+    fn reduce(&mut self, prod: usize) -> Result<(), String> {
+        let parser = self.parser;
+        let rule = &parser.grammar.rules()[prod];
+        let new_root = self.tree.new_node(parser.grammar.symbols().symbol(rule.from).clone());
+        self.tree.set_root(new_root);
+
+
+        let to_pop = rule.to.len() * 2;
+        let stack_len = self.stack.len();
+
+        if to_pop > stack_len {
+            return Err("Reduce Error: empty stack".to_string());
         }
+
+        let popped = Rc::make_mut(&mut self.stack).split_off(stack_len - to_pop);
+        for el in popped.into_iter().filter(|el| el.is_symbol()) {
+            let (_, child_id) = el.unwrap_symbol();
+            self.tree.append(new_root, child_id.expect("Unexpected EOF"));
+        }
+
+
+        let state = self.get_stacktop_state()?;
+        let next = parser.get_single_goto(state, rule.from)?;
+        let stack = Rc::make_mut(&mut self.stack);
+        stack.push(StackEl::Symbol((rule.from, Some(new_root))));
+        stack.push(StackEl::State(next));
+        Ok(())
+    }
+
+    fn get_single_action(
+        &self,
+        state: usize,
+        terminal: SymbolId,
+        word: &dyn TokenLike,
+    ) -> Result<Action, ParseError> {
+        let parser = self.parser;
+        let message = match parser.action_entry(state, terminal) {
+            ActionEntry::Action(action) => return Ok(action),
+            ActionEntry::Error => return Err(self.unexpected(word)),
+            ActionEntry::Conflict(actions) => {
+                let conflict = || parser.conflict(state, terminal, actions).to_string(&parser.grammar);
+                match parser.resolver {
+                    Some(Resolver(ref resolver)) => {
+                        match resolver.resolve(state, word, actions, &self.stack) {
+                            Some(action) if actions.contains(&action) => return Ok(action),
+                            Some(action) => format!(
+                                "Conflict resolver chose {:?}, which is not one of {:?}",
+                                action,
+                                actions
+                            ),
+                            None => format!("Conflict resolver rejected {}", conflict()),
+                        }
+                    }
+                    None => format!("Found conflicts in the Action table: {}", conflict()),
+                }
+            }
+        };
+        Err(ParseError::Conflict {
+            index: self.fed,
+            state,
+            message,
+        })
+    }
+
+    fn get_stacktop_state(&self) -> Result<usize, String> {
+        self.stack
+            .last()
+            .ok_or_else(|| "Empty stack".to_string())
+            .and_then(|el| match *el {
+                StackEl::State(s) => Ok(s),
+                _ => Err("Attempting to read an invalid state from stack".to_string()),
+            })
+    }
+
+    pub fn stack_to_string(&self) -> String {
+        self.stack
+            .iter()
+            .map(|el| match *el {
+                StackEl::Symbol((s, ref node_id)) => {
+                    format!("{} {:?}", self.parser.grammar.symbols().symbol(s), node_id)
+                }
+                StackEl::State(s) => s.to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::super::{FAKE_GOAL, EOF};
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::thread;
+    use super::super::{FAKE_GOAL, EOF, LAMBDA, Symbol, LrItem, BitSet};
+    use super::super::test_util::*;
 
     #[test]
     fn closure_and_goto_test() {
         let parser = example_parser();
         let g = &parser.grammar;
-        let first_prod = parser.grammar.productions[0].clone();
-        let item = Item::from_production(first_prod, Symbol::T(EOF.to_string()));
-        let items: BTreeSet<Item> = vec![item].iter().cloned().collect();
-        let cc0 = parser.closure(&items);
+        let eof = SymbolTable::eof().index();
+        let kernel = vec![LrItem::new(0, 0, BitSet::singleton(eof))];
+        let automaton = parser.automaton().unwrap();
+        let cc0 = automaton.closure(g, &kernel);
 
-        let actual = &cc0;
-        let expected = vec![
-            Item::from_str(FAKE_GOAL, vec!["List"], 0, EOF, g),
+        let actual = &automaton.expand(&cc0);
+        let expected = [Item::from_str(FAKE_GOAL, vec!["List"], 0, EOF, g),
             Item::from_str("List", vec!["List", "Pair"], 0, EOF, g),
             Item::from_str("List", vec!["List", "Pair"], 0, "(", g),
             Item::from_str("List", vec!["Pair"], 0, EOF, g),
@@ -548,42 +938,38 @@ mod tests {
             Item::from_str("Pair", vec!["(", "Pair", ")"], 0, EOF, g),
             Item::from_str("Pair", vec!["(", "Pair", ")"], 0, "(", g),
             Item::from_str("Pair", vec!["(", ")"], 0, EOF, g),
-            Item::from_str("Pair", vec!["(", ")"], 0, "(", g),
-        ].iter()
+            Item::from_str("Pair", vec!["(", ")"], 0, "(", g)].iter()
             .cloned()
             .collect();
 
-        let expected = Rc::new(expected);
-
         assert_eq!(
             actual,
             &expected,
             "\n\n>>>actual {}\n>>>expected {}",
-            Item::set_to_string(&actual),
-            Item::set_to_string(&expected)
+            Item::set_to_string(actual, &parser.grammar),
+            Item::set_to_string(&expected, &parser.grammar)
         );
 
-        let actual = parser.goto(&cc0, &Symbol::T("(".to_string())).unwrap();
-        let expected = vec![
-            Item::from_str("Pair", vec!["(", "Pair", ")"], 1, EOF, g),
+        let open = g.symbols().terminal_id("(").unwrap();
+        let next = automaton.goto(g, &cc0, open).unwrap();
+        let actual = automaton.expand(&automaton.closure(g, &next));
+        let expected = [Item::from_str("Pair", vec!["(", "Pair", ")"], 1, EOF, g),
             Item::from_str("Pair", vec!["(", "Pair", ")"], 1, "(", g),
 
             Item::from_str("Pair", vec!["(", ")"], 1, EOF, g),
             Item::from_str("Pair", vec!["(", ")"], 1, "(", g),
 
             Item::from_str("Pair", vec!["(", "Pair", ")"], 0, ")", g),
-            Item::from_str("Pair", vec!["(", ")"], 0, ")", g),
-        ].iter()
+            Item::from_str("Pair", vec!["(", ")"], 0, ")", g)].iter()
             .cloned()
             .collect();
-        let expected = Rc::new(expected);
 
         assert_eq!(
             actual,
             expected,
             "\n\n>>>actual {}\n>>>expected {}",
-            Item::set_to_string(&actual),
-            Item::set_to_string(&expected)
+            Item::set_to_string(&actual, &parser.grammar),
+            Item::set_to_string(&expected, &parser.grammar)
         );
     }
 
@@ -591,16 +977,16 @@ mod tests {
     fn goto_test2() {
         use Symbol::*;
         let parser = example_parser();
+        let g = &parser.grammar;
+        let automaton = parser.automaton().unwrap();
         let cc_vec = paretheses_cc();
 
-        let col = vec![
-            NT("Goal".to_string()),
+        let col = [NT("Goal".to_string()),
             NT("List".to_string()),
             NT("Pair".to_string()),
             T("(".to_string()),
             T(")".to_string()),
-            Symbol::eof(),
-        ];
+            Symbol::eof()];
 
         let expected = vec![
             [
@@ -652,12 +1038,19 @@ mod tests {
 
         for (i, row) in expected.iter().enumerate() {
             for (j, e) in row.iter().enumerate() {
-                let a = parser.goto(&cc_vec[i], &col[j]);
+                let kernel = &automaton.states[state_of(&parser, &cc_vec[i])].kernel;
+                let items = automaton.closure(g, kernel);
+                let a = parser
+                    .grammar
+                    .symbols()
+                    .id(&col[j])
+                    .and_then(|x| automaton.goto(g, &items, x))
+                    .map(|next| Rc::new(automaton.expand(&automaton.closure(g, &next))));
 
                 assert_eq!(a.clone(), e.clone(), "\nFrom {:?}\nActual {:?}\nExpected {:?}",
-                           Item::set_to_string(&cc_vec[i]),
-                           a.clone().map(|a| Item::set_to_string(&a)),
-                           e.clone().map(|e| Item::set_to_string(&e)),
+                           Item::set_to_string(&cc_vec[i], &parser.grammar),
+                           a.clone().map(|a| Item::set_to_string(&a, &parser.grammar)),
+                           e.clone().map(|e| Item::set_to_string(&e, &parser.grammar)),
                         );
             }
         }
@@ -668,75 +1061,68 @@ mod tests {
         use Action::*;
         let parser = example_parser();
         let cc_vec = paretheses_cc();
-        let prods = parser.grammar.productions.clone();
+        let state = |i: usize| state_of(&parser, &cc_vec[i]);
+        let terminal = |t: &str| parser.grammar.symbols().terminal_id(t).unwrap();
 
 
         let col = [EOF, "(", ")"];
 
         let expected = vec![
             //0
-            [None, Some(Shift(cc_vec[3].clone())), None],
-            [Some(Accept), Some(Shift(cc_vec[3].clone())), None],
+            [None, Some(Shift(state(3))), None],
+            [Some(Accept), Some(Shift(state(3))), None],
             //2
             [
-                Some(Reduce(prods[2].clone())),
-                Some(Reduce(prods[2].clone())),
+                Some(Reduce(2)),
+                Some(Reduce(2)),
                 None,
             ],
             [
                 None,
-                Some(Shift(cc_vec[6].clone())),
-                Some(Shift(cc_vec[7].clone())),
+                Some(Shift(state(6))),
+                Some(Shift(state(7))),
             ],
             //4
             [
-                Some(Reduce(prods[1].clone())),
-                Some(Reduce(prods[1].clone())),
+                Some(Reduce(1)),
+                Some(Reduce(1)),
                 None,
             ],
-            [None, None, Some(Shift(cc_vec[8].clone()))],
+            [None, None, Some(Shift(state(8)))],
             //6
             [
                 None,
-                Some(Shift(cc_vec[6].clone())),
-                Some(Shift(cc_vec[10].clone())),
+                Some(Shift(state(6))),
+                Some(Shift(state(10))),
             ],
             [
-                Some(Reduce(prods[4].clone())),
-                Some(Reduce(prods[4].clone())),
+                Some(Reduce(4)),
+                Some(Reduce(4)),
                 None,
             ],
             //8
             [
-                Some(Reduce(prods[3].clone())),
-                Some(Reduce(prods[3].clone())),
+                Some(Reduce(3)),
+                Some(Reduce(3)),
                 None,
             ],
-            [None, None, Some(Shift(cc_vec[11].clone()))],
+            [None, None, Some(Shift(state(11)))],
             //10
-            [None, None, Some(Reduce(prods[4].clone()))],
-            [None, None, Some(Reduce(prods[3].clone()))],
+            [None, None, Some(Reduce(4))],
+            [None, None, Some(Reduce(3))],
         ];
 
         for (i, row) in expected.iter().enumerate() {
             for (j, e) in row.iter().enumerate() {
-                let a = parser.action.get(&(
-                    cc_vec[i].clone(),
-                    Symbol::T(col[j].to_string()),
-                ));
-
-                let e = e.clone().map(|a| {
-                    let mut set = BTreeSet::new();
-                    set.insert(a.clone());
-                    set
-                });
+                let a = parser.table.actions(state(i), terminal(col[j]));
+                let e: Vec<Action> = e.iter().cloned().collect();
 
                 assert_eq!(
-                    a.map(|a| a.clone()),
-                    e.clone(),
+                    a,
+                    e,
                     "\n>>>Actual {:?} \n>>>Expected {:?}",
-                    a.map(|a| parser.set_of_actions_to_string(&a)),
-                    e.map(|a| parser.set_of_actions_to_string(&a))
+                    parser.set_of_actions_to_string(&a),
+                    parser.set_of_actions_to_string(&e)
                 );
             }
         }
@@ -746,14 +1132,16 @@ mod tests {
     fn build_cc_test() {
         let parser = example_parser();
         let expected_cc: BTreeSet<Rc<BTreeSet<Item>>> = paretheses_cc().into_iter().collect();
-        let actual_cc = parser.cc.clone();
+        let actual_cc: BTreeSet<Rc<BTreeSet<Item>>> = (0..parser.table.state_count())
+            .map(|i| Rc::new(parser.state_items(i).unwrap()))
+            .collect();
 
         assert_eq!(
             actual_cc.len(),
             expected_cc.len(),
             "Should have the same length \nACTUAL   {}\nEXPECTED {}",
-            Item::set_of_sets_to_string(&actual_cc),
-            Item::set_of_sets_to_string(&expected_cc)
+            Item::set_of_sets_to_string(&actual_cc, &parser.grammar),
+            Item::set_of_sets_to_string(&expected_cc, &parser.grammar)
         );
 
         for (actual_items, expected_items) in actual_cc.iter().zip(&expected_cc) {
@@ -761,8 +1149,8 @@ mod tests {
                 actual_items,
                 expected_items,
                 "\n>>>Actual {}\n>>>Expected {}",
-                Item::set_to_string(actual_items),
-                Item::set_to_string(expected_items)
+                Item::set_to_string(actual_items, &parser.grammar),
+                Item::set_to_string(expected_items, &parser.grammar)
             );
         }
 
@@ -780,15 +1168,14 @@ mod tests {
 
     #[test]
     fn parse_test() {
-        fn lex(tokens: &str) -> Vec<Box<TokenLike>> {
-            if tokens.len() == 0 {
+        fn lex(tokens: &str) -> Vec<Box<dyn TokenLike>> {
+            if tokens.is_empty() {
                 return vec![];
             }
             tokens
-                .split(" ")
-                .into_iter()
+                .split(' ')
                 .map(|s| {
-                    Box::new((s.to_string(), "".to_string())) as Box<TokenLike>
+                    Box::new((s.to_string(), "".to_string())) as Box<dyn TokenLike>
                 })
                 .collect()
         }
@@ -822,6 +1209,247 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_without_automaton_test() {
+        let mut parser = example_parser();
+        parser.drop_automaton();
+        assert!(parser.automaton().is_none());
+
+        let tokens = "( ( ) ) ( ) EOF".split(' ').map(|s| {
+            Box::new((s.to_string(), "".to_string())) as Box<dyn TokenLike>
+        });
+        assert!(parser.parse(tokens).is_ok());
+
+        let tokens = "( ( ) EOF".split(' ').map(|s| {
+            Box::new((s.to_string(), "".to_string())) as Box<dyn TokenLike>
+        });
+        assert!(parser.parse(tokens).is_err());
+    }
+
+    #[test]
+    fn parse_lambda_test() {
+        let g = Grammar::from_str(
+            "S",
+            vec!["S"],
+            vec![("S", vec!["(", "S", ")", "S"]), ("S", vec![LAMBDA])],
+        );
+        let parser = Parser::new(g);
+        assert!(parser.is_lr1());
+
+        for case in &["( ) EOF", "( ( ) ) ( ) EOF"] {
+            let tokens = case.split(' ').map(|s| {
+                Box::new((s.to_string(), "".to_string())) as Box<dyn TokenLike>
+            });
+            let res = parser.parse(tokens);
+            assert!(res.is_ok(), "case {:?}, res {}", case, res.err().unwrap());
+        }
+    }
+
+    fn statements_grammar() -> Grammar {
+        Grammar::from_str(
+            "List",
+            vec!["List", "Stmt"],
+            vec![
+                ("List", vec!["List", "Stmt"]),
+                ("List", vec!["Stmt"]),
+                ("Stmt", vec!["id", "=", "num", ";"]),
+            ],
+        )
+    }
+
+    #[test]
+    fn default_reductions_test() {
+        // The stack once the tokens run out after the first statement.
+        fn stack_after_statement(parser: &Parser) -> String {
+            let mut session = parser.session();
+            let err = session.parse(lex("id = num ;")).err().unwrap();
+            assert!(matches!(err, ParseError::UnexpectedEnd { .. }), "{:?}", err);
+            session.stack_to_string()
+        }
+
+        let mut parser = Parser::new(statements_grammar());
+        assert_eq!(parser.default_reduction(0), None);
+        let before = stack_after_statement(&parser);
+        assert!(!before.contains("List"), "{}", before);
+
+        let defaults = parser.table().default_reductions();
+        assert_eq!(parser.use_default_reductions(), 3);
+        let mut prods: Vec<usize> = defaults.iter().filter_map(|&prod| prod).collect();
+        prods.sort();
+        assert_eq!(prods, vec![1, 2, 3]);
+
+        // The first statement is reduced before the next one is read.
+        let after = stack_after_statement(&parser);
+        assert!(after.contains("List"), "{}", after);
+
+        assert!(parser.parse(lex("id = num ; num EOF")).is_err());
+    }
+
+    #[test]
+    fn shared_parser_test() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Parser>();
+
+        let parser = Arc::new(example_parser());
+        let handles: Vec<_> = (1..9)
+            .map(|n| {
+                let parser = parser.clone();
+                thread::spawn(move || {
+                    let case = format!("{}{} EOF", "( ".repeat(n), ") ".repeat(n).trim());
+                    parser.parse(lex(&case)).map(|tree| tree.to_string())
+                })
+            })
+            .collect();
+
+        for (n, handle) in (1..9).zip(handles) {
+            let tree = handle.join().unwrap().unwrap();
+            assert_eq!(tree.matches("NT(\"Pair\")").count(), n);
+        }
+    }
+
+    #[test]
+    fn nested_parse_test() {
+        // Parses another input every time the outer parse reads a token.
+        let parser = example_parser();
+        let expected = parser.parse(lex("( ( ) ) EOF")).unwrap().to_string();
+        let tokens = lex("( ) ( ) EOF").inspect(|_| {
+            let inner = parser.parse(lex("( ( ) ) EOF")).unwrap();
+            assert_eq!(inner.to_string(), expected);
+        });
+        assert!(parser.parse(tokens).is_ok());
+    }
+
+    #[test]
+    fn feed_test() {
+        let parser = example_parser();
+        let expected = parser.parse(lex("( ) ( ) EOF")).unwrap();
+
+        // Two sessions paused in turns on the same parser.
+        let mut first = parser.session();
+        let mut second = parser.session();
+        for t in &["(", ")", "("] {
+            assert_eq!(first.feed(token(t)), Feed::NeedMore);
+            assert_eq!(second.feed(token(t)), Feed::NeedMore);
+        }
+        assert_eq!(first.feed(token(")")), Feed::NeedMore);
+        assert_eq!(first.feed(token(EOF)), Feed::Accepted);
+        assert_eq!(first.finish().unwrap().to_string(), expected.to_string());
+
+        let err = second.finish().err().unwrap();
+        assert_eq!(err.to_string(), "expected `(` or `)`, found end of input");
+        match second.feed(token(EOF)) {
+            Feed::Error(ParseError::UnexpectedToken { index, ref expected, .. }) => {
+                assert_eq!(index, 3);
+                assert_eq!(expected, &["(", ")"]);
+            }
+            feed => panic!("Unexpected {:?}", feed),
+        }
+        // Rejected sessions stay rejected.
+        assert!(matches!(second.feed(token(")")), Feed::Error(_)));
+        assert!(second.finish().is_err());
+
+        assert!(parser.session().finish().unwrap().root().is_none());
+        let mut session = parser.session();
+        match session.feed(token("x")) {
+            Feed::Error(err) => assert_eq!(err.to_string(), "expected `(`, found `x`"),
+            feed => panic!("Unexpected {:?}", feed),
+        }
+    }
+
+    #[test]
+    fn checkpoint_test() {
+        let parser = example_parser();
+        let expected = parser.parse(lex("( ) ( ( ) ) EOF")).unwrap();
+
+        let mut session = parser.session();
+        assert_eq!(session.speculate(tokens("( )")), Feed::NeedMore);
+        let stack = session.stack().to_vec();
+        let tree_len = session.tree.len();
+
+        // The second ) is rejected, and the session goes back to `stack`.
+        match session.speculate(tokens("( ) )")) {
+            Feed::Error(err) => assert_eq!(err.to_string(), "expected `EOF` or `(`, found `)`"),
+            feed => panic!("Unexpected {:?}", feed),
+        }
+        assert_eq!(session.stack(), &stack[..]);
+        assert_eq!(session.tree.len(), tree_len);
+
+        // Accepting can be undone too.
+        let checkpoint = session.checkpoint();
+        assert_eq!(session.speculate(tokens("EOF")), Feed::Accepted);
+        session.restore(&checkpoint).unwrap();
+        assert_eq!(session.speculate(tokens("( ( ) ) EOF")), Feed::Accepted);
+        assert_eq!(session.finish().unwrap().to_string(), expected.to_string());
+
+        // The tree is handed out once, and earlier checkpoints go with it.
+        assert!(matches!(session.finish(), Err(ParseError::Internal(_))));
+        assert!(session.restore(&checkpoint).is_err());
+
+        let mut session = parser.session();
+        assert_eq!(session.speculate(tokens("( )")), Feed::NeedMore);
+        let checkpoint = session.checkpoint();
+        assert!(session.finish().is_err());
+        session.restore(&checkpoint).unwrap();
+        let tree = session.parse(lex("( ) EOF")).unwrap();
+        assert!(tree.root().is_some());
+        assert!(session.restore(&checkpoint).is_err());
+    }
+
+    #[test]
+    fn acceptable_test() {
+        let mut parser = example_parser();
+        for compressed in &[false, true] {
+            if *compressed {
+                parser.compress();
+                parser.use_default_reductions();
+            }
+            let symbols = parser.grammar().symbols();
+            let kinds = |session: &ParseSession| -> Vec<String> {
+                session.acceptable().into_iter().map(|t| symbols.symbol(t).as_str().to_string()).collect()
+            };
+
+            // EOF alone is only accepted by `feed`, as an empty input.
+            let mut session = parser.session();
+            assert_eq!(kinds(&session), vec!["("]);
+            session.feed(token("("));
+            assert_eq!(kinds(&session), vec!["(", ")"]);
+            session.feed(token(")"));
+            // EOF only after reducing the pair and the list.
+            assert_eq!(kinds(&session), vec![EOF, "("]);
+            assert!(!session.accepts(symbols.terminal_id(")").unwrap()));
+
+            session.feed(token(EOF));
+            assert!(session.acceptable().is_empty());
+        }
+    }
+
+    #[test]
+    fn completeness_test() {
+        let parser = Parser::new(statements_grammar());
+        let cases = vec![
+            ("id = num ;", Completeness::Complete),
+            ("id = num ; id = num ;", Completeness::Complete),
+            ("id = num ; EOF", Completeness::Complete),
+            ("id", Completeness::Incomplete),
+            ("id = num ; id = num", Completeness::Incomplete),
+            ("id = ;", Completeness::Invalid(2)),
+            ("id = num ; ; id", Completeness::Invalid(4)),
+            ("id = x", Completeness::Invalid(2)),
+            // The grammar does not derive the empty string.
+            ("", Completeness::Incomplete),
+            ("EOF", Completeness::Invalid(0)),
+            ("id = EOF", Completeness::Invalid(2)),
+        ];
+        for (case, expected) in cases {
+            assert_eq!(parser.completeness(lex(case)), expected, "{}", case);
+        }
+
+        let parser = Parser::new(balanced_grammar());
+        assert_eq!(parser.completeness(lex("")), Completeness::Complete);
+        assert_eq!(parser.completeness(lex("EOF")), Completeness::Complete);
+        assert_eq!(parser.completeness(lex("( (")), Completeness::Incomplete);
+    }
+
     fn paretheses_grammar() -> Grammar {
         let non_terminals = vec!["List", "Pair"];
 
@@ -833,8 +1461,14 @@ mod tests {
             ("Pair", vec!["(", ")"]),
         ];
 
-        let g = Grammar::from_str("List", non_terminals, prods);
-        g
+        
+        Grammar::from_str("List", non_terminals, prods)
+    }
+
+    fn state_of(parser: &Parser, items: &BTreeSet<Item>) -> usize {
+        (0..parser.table.state_count())
+            .find(|&i| parser.state_items(i).as_ref() == Some(items))
+            .expect("Unexpected item set")
     }
 
     fn example_parser() -> Parser {
@@ -844,8 +1478,7 @@ mod tests {
 
     fn paretheses_cc() -> Vec<Rc<BTreeSet<Item>>> {
         let g = paretheses_grammar().with_fake_goal();
-        let cc0 = vec![
-            Item::from_str(FAKE_GOAL, vec!["List"], 0, EOF, &g),
+        let cc0 = [Item::from_str(FAKE_GOAL, vec!["List"], 0, EOF, &g),
             Item::from_str("List", vec!["List", "Pair"], 0, EOF, &g),
             Item::from_str("List", vec!["List", "Pair"], 0, "(", &g),
             Item::from_str("List", vec!["Pair"], 0, EOF, &g),
@@ -854,13 +1487,11 @@ mod tests {
             Item::from_str("Pair", vec!["(", "Pair", ")"], 0, EOF, &g),
             Item::from_str("Pair", vec!["(", "Pair", ")"], 0, "(", &g),
             Item::from_str("Pair", vec!["(", ")"], 0, EOF, &g),
-            Item::from_str("Pair", vec!["(", ")"], 0, "(", &g),
-        ].iter()
+            Item::from_str("Pair", vec!["(", ")"], 0, "(", &g)].iter()
             .cloned()
             .collect();
 
-        let cc1 = vec![
-            Item::from_str(FAKE_GOAL, vec!["List"], 1, EOF, &g),
+        let cc1 = [Item::from_str(FAKE_GOAL, vec!["List"], 1, EOF, &g),
 
             Item::from_str("List", vec!["List", "Pair"], 1, EOF, &g),
             Item::from_str("List", vec!["List", "Pair"], 1, "(", &g),
@@ -869,86 +1500,71 @@ mod tests {
             Item::from_str("Pair", vec!["(", "Pair", ")"], 0, "(", &g),
 
             Item::from_str("Pair", vec!["(", ")"], 0, EOF, &g),
-            Item::from_str("Pair", vec!["(", ")"], 0, "(", &g),
-        ].iter()
+            Item::from_str("Pair", vec!["(", ")"], 0, "(", &g)].iter()
             .cloned()
             .collect();
 
-        let cc2 = vec![
-            Item::from_str("List", vec!["Pair"], 1, EOF, &g),
-            Item::from_str("List", vec!["Pair"], 1, "(", &g),
-        ].iter()
+        let cc2 = [Item::from_str("List", vec!["Pair"], 1, EOF, &g),
+            Item::from_str("List", vec!["Pair"], 1, "(", &g)].iter()
             .cloned()
             .collect();
 
-        let cc3 = vec![
-            Item::from_str("Pair", vec!["(", "Pair", ")"], 0, ")", &g),
+        let cc3 = [Item::from_str("Pair", vec!["(", "Pair", ")"], 0, ")", &g),
             Item::from_str("Pair", vec!["(", "Pair", ")"], 1, EOF, &g),
             Item::from_str("Pair", vec!["(", "Pair", ")"], 1, "(", &g),
 
             Item::from_str("Pair", vec!["(", ")"], 0, ")", &g),
             Item::from_str("Pair", vec!["(", ")"], 1, EOF, &g),
-            Item::from_str("Pair", vec!["(", ")"], 1, "(", &g),
-        ].iter()
+            Item::from_str("Pair", vec!["(", ")"], 1, "(", &g)].iter()
             .cloned()
             .collect();
 
-        let cc4 = vec![
-            Item::from_str("List", vec!["List", "Pair"], 2, EOF, &g),
-            Item::from_str("List", vec!["List", "Pair"], 2, "(", &g),
-        ].iter()
+        let cc4 = [Item::from_str("List", vec!["List", "Pair"], 2, EOF, &g),
+            Item::from_str("List", vec!["List", "Pair"], 2, "(", &g)].iter()
             .cloned()
             .collect();
 
-        let cc5 = vec![
-            Item::from_str("Pair", vec!["(", "Pair", ")"], 2, EOF, &g),
-            Item::from_str("Pair", vec!["(", "Pair", ")"], 2, "(", &g),
-        ].iter()
+        let cc5 = [Item::from_str("Pair", vec!["(", "Pair", ")"], 2, EOF, &g),
+            Item::from_str("Pair", vec!["(", "Pair", ")"], 2, "(", &g)].iter()
             .cloned()
             .collect();
 
-        let cc6 = vec![
-            Item::from_str("Pair", vec!["(", "Pair", ")"], 0, ")", &g),
+        let cc6 = [Item::from_str("Pair", vec!["(", "Pair", ")"], 0, ")", &g),
             Item::from_str("Pair", vec!["(", "Pair", ")"], 1, ")", &g),
             Item::from_str("Pair", vec!["(", ")"], 0, ")", &g),
-            Item::from_str("Pair", vec!["(", ")"], 1, ")", &g),
-        ].iter()
+            Item::from_str("Pair", vec!["(", ")"], 1, ")", &g)].iter()
             .cloned()
             .collect();
 
-        let cc7 = vec![
-            Item::from_str("Pair", vec!["(", ")"], 2, EOF, &g),
-            Item::from_str("Pair", vec!["(", ")"], 2, "(", &g),
-        ].iter()
+        let cc7 = [Item::from_str("Pair", vec!["(", ")"], 2, EOF, &g),
+            Item::from_str("Pair", vec!["(", ")"], 2, "(", &g)].iter()
             .cloned()
             .collect();
 
-        let cc8 = vec![
-            Item::from_str("Pair", vec!["(", "Pair", ")"], 3, EOF, &g),
-            Item::from_str("Pair", vec!["(", "Pair", ")"], 3, "(", &g),
-        ].iter()
+        let cc8 = [Item::from_str("Pair", vec!["(", "Pair", ")"], 3, EOF, &g),
+            Item::from_str("Pair", vec!["(", "Pair", ")"], 3, "(", &g)].iter()
             .cloned()
             .collect();
 
-        let cc9 = vec![Item::from_str("Pair", vec!["(", "Pair", ")"], 2, ")", &g)]
+        let cc9 = [Item::from_str("Pair", vec!["(", "Pair", ")"], 2, ")", &g)]
             .iter()
             .cloned()
             .collect();
 
-        let cc10 = vec![Item::from_str("Pair", vec!["(", ")"], 2, ")", &g)]
+        let cc10 = [Item::from_str("Pair", vec!["(", ")"], 2, ")", &g)]
             .iter()
             .cloned()
             .collect();
 
-        let cc11 = vec![Item::from_str("Pair", vec!["(", "Pair", ")"], 3, ")", &g)]
+        let cc11 = [Item::from_str("Pair", vec!["(", "Pair", ")"], 3, ")", &g)]
             .iter()
             .cloned()
             .collect();
 
-        vec![cc0, cc1, cc2, cc3, cc4, cc5, cc6, cc7, cc8, cc9, cc10, cc11]
+        [cc0, cc1, cc2, cc3, cc4, cc5, cc6, cc7, cc8, cc9, cc10, cc11]
             .iter()
             .cloned()
-            .map(|s| Rc::new(s))
+            .map(Rc::new)
             .collect()
     }
 }
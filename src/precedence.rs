@@ -0,0 +1,178 @@
+use super::{Action, Grammar, SymbolId};
+
+/// Associativity of a precedence level, as in yacc's `%left`, `%right` and
+/// `%nonassoc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Assoc {
+    Left,
+    Right,
+    NonAssoc,
+}
+
+/// Resolves the shift/reduce conflicts of a cell with precedences, the way
+/// yacc does.
+///
+/// Every reduction is compared with the shift on `terminal`, in production
+/// order, provided both have a precedence: the higher one wins, and on a tie
+/// left associativity reduces, right associativity shifts and `nonassoc`
+/// drops both (making the cell an error). Anything else, an accept included,
+/// is left as it is.
+pub(crate) fn resolve(g: &Grammar, terminal: SymbolId, actions: &[Action]) -> Vec<Action> {
+    let shift_prec = match g.terminal_precedence(terminal) {
+        Some(prec) => prec,
+        None => return actions.to_vec(),
+    };
+    let mut shift = actions.iter().cloned().find(|action| matches!(*action, Action::Shift(_)));
+    if shift.is_none() {
+        return actions.to_vec();
+    }
+
+    let mut kept = Vec::new();
+    for &action in actions {
+        let prod = match action {
+            Action::Reduce(prod) => prod,
+            Action::Shift(_) => continue,
+            Action::Accept => {
+                kept.push(action);
+                continue;
+            }
+        };
+
+        let (level, assoc) = match g.production_precedence(prod) {
+            Some(prec) if shift.is_some() => prec,
+            _ => {
+                kept.push(action);
+                continue;
+            }
+        };
+
+        if level > shift_prec.0 || (level == shift_prec.0 && assoc == Assoc::Left) {
+            shift = None;
+            kept.push(action);
+        } else if level == shift_prec.0 && assoc == Assoc::NonAssoc {
+            shift = None;
+        }
+    }
+
+    kept.extend(shift);
+    kept.sort();
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Parser, SymbolTable, EOF};
+    use super::super::test_util::*;
+
+    fn expr_grammar() -> Grammar {
+        let mut g = Grammar::from_str(
+            "E",
+            vec!["E"],
+            vec![
+                ("E", vec!["E", "+", "E"]),
+                ("E", vec!["E", "-", "E"]),
+                ("E", vec!["E", "*", "E"]),
+                ("E", vec!["E", "^", "E"]),
+                ("E", vec!["E", "<", "E"]),
+                ("E", vec!["-", "E"]),
+                ("E", vec!["num"]),
+            ],
+        );
+        g.declare_precedence(Assoc::NonAssoc, &["<"]);
+        g.declare_precedence(Assoc::Left, &["+", "-"]);
+        g.declare_precedence(Assoc::Left, &["*"]);
+        g.declare_precedence(Assoc::Right, &["^"]);
+        g.declare_precedence(Assoc::Right, &["UMINUS"]);
+        g.set_production_precedence(5, "UMINUS");
+        g
+    }
+
+    #[test]
+    fn precedence_test() {
+        let g = expr_grammar();
+        assert_eq!(g.production_precedence(0), Some((2, Assoc::Left)));
+        assert_eq!(g.production_precedence(5), Some((5, Assoc::Right)));
+        assert_eq!(g.production_precedence(6), None);
+
+        let parser = Parser::new(g);
+        assert!(parser.is_lr1(), "{:?}", parser.conflicts());
+
+        let first_child = |s: &str| -> String {
+            let tree = parser.parse(lex(s)).unwrap();
+            let root = tree.node(tree.root().unwrap()).unwrap();
+            let mut leaves = Vec::new();
+            collect_leaves(&tree, root.children[0], &mut leaves);
+            leaves.join(" ")
+        };
+
+        // `*` binds tighter than `+`, and both associate to the left.
+        assert_eq!(first_child("num + num * num EOF"), "num");
+        assert_eq!(first_child("num * num + num EOF"), "num * num");
+        assert_eq!(first_child("num - num - num EOF"), "num - num");
+        // `^` associates to the right.
+        assert_eq!(first_child("num ^ num ^ num EOF"), "num");
+        // Unary minus binds tighter than everything.
+        assert_eq!(first_child("- num ^ num EOF"), "- num");
+
+        // `<` is nonassoc.
+        assert!(parser.parse(lex("num < num EOF")).is_ok());
+        assert!(parser.parse(lex("num < num < num EOF")).is_err());
+    }
+
+    fn collect_leaves(tree: &super::super::Tree, id: usize, leaves: &mut Vec<String>) {
+        let node = tree.node(id).unwrap();
+        if node.children.is_empty() {
+            leaves.push(node.data.to_string().split(' ').next().unwrap().to_string());
+        }
+        for &child in &node.children {
+            collect_leaves(tree, child, leaves);
+        }
+    }
+
+    #[test]
+    fn accept_test() {
+        // Only shifts and reductions are compared: an accept stays.
+        let mut g = sum_grammar();
+        g.declare_precedence(Assoc::Left, &[EOF, "+"]);
+        let g = g.with_fake_goal();
+        let actions = [Action::Accept, Action::Reduce(1), Action::Shift(3)];
+        assert_eq!(resolve(&g, SymbolTable::eof(), &actions), vec![Action::Accept, Action::Reduce(1)]);
+    }
+
+    #[test]
+    fn unresolved_test() {
+        // Without a precedence for `*`, only `E + E` against `+` is resolved.
+        let mut g = Grammar::from_str(
+            "E",
+            vec!["E"],
+            vec![
+                ("E", vec!["E", "+", "E"]),
+                ("E", vec!["E", "*", "E"]),
+                ("E", vec!["num"]),
+            ],
+        );
+        g.declare_precedence(Assoc::Left, &["+"]);
+        let parser = Parser::new(g);
+
+        let plus = parser.grammar().symbols().terminal_id("+").unwrap();
+        let conflicts = parser.conflicts();
+        assert!(!conflicts.is_empty());
+        assert!(conflicts.iter().all(|conflict| {
+            conflict.lookahead != plus || conflict.productions != vec![1]
+        }));
+    }
+
+    #[test]
+    fn fingerprint_test() {
+        let plain = sum_grammar();
+        let mut left = sum_grammar();
+        left.declare_precedence(Assoc::Left, &["+"]);
+        let mut right = sum_grammar();
+        right.declare_precedence(Assoc::Right, &["+"]);
+
+        assert!(plain.fingerprint() != left.fingerprint());
+        assert!(left.fingerprint() != right.fingerprint());
+        assert_eq!(left.with_fake_goal().production_precedence(1), Some((1, Assoc::Left)));
+    }
+}
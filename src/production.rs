@@ -1,6 +1,6 @@
 use std::fmt;
 
-use super::Symbol;
+use super::{Symbol, SymbolId};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Production {
@@ -10,7 +10,7 @@ pub struct Production {
 
 impl Production {
     pub fn new(from: Symbol, to: Vec<Symbol>) -> Production {
-        Production { from: from, to: to }
+        Production { from, to }
     }
 }
 
@@ -28,3 +28,13 @@ impl fmt::Display for Production {
         )
     }
 }
+
+/// A `Production` with its symbols interned by the owning `Grammar`.
+///
+/// `LAMBDA` is dropped from the right hand side, so `X -> LAMBDA` becomes the
+/// empty rule.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rule {
+    pub from: SymbolId,
+    pub to: Vec<SymbolId>,
+}
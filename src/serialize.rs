@@ -0,0 +1,351 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use super::{Conflict, Grammar, Parser, Production, Symbol, SymbolId, Table};
+use super::table::{pack, unpack, valid_conflict_action};
+
+// Bumped whenever the format below changes; older files are rejected.
+const FORMAT_VERSION: u32 = 2;
+
+// A saved parser is a line based text file:
+//
+//     lr1 <version>
+//     fingerprint <hex>
+//     goal <symbol>
+//     prod <symbol> -> <symbol> ...     (one per production, fake goal first)
+//     table ...                         (see Table::write)
+//     resolved <count>
+//     conflict <state> <terminal> <packed action> ...
+//                                       (one per conflict resolved by default)
+//     options [compressed] [default_reductions]
+//     end
+//
+// Symbols are written as `T:name` or `N:name`, with whitespace and
+// backslashes escaped.
+
+fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ' ' => escaped.push_str("\\s"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(escaped: &str) -> Result<String, String> {
+    let mut name = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            name.push(c);
+            continue;
+        }
+
+        name.push(match chars.next() {
+            Some('\\') => '\\',
+            Some('s') => ' ',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            _ => return Err(format!("Invalid escape in symbol {:?}", escaped)),
+        });
+    }
+    Ok(name)
+}
+
+fn write_symbol(s: &Symbol) -> String {
+    let kind = if s.is_terminal() { "T" } else { "N" };
+    format!("{}:{}", kind, escape(s.as_str()))
+}
+
+fn read_symbol(field: &str) -> Result<Symbol, String> {
+    if let Some(name) = field.strip_prefix("T:") {
+        Ok(Symbol::T(unescape(name)?))
+    } else if let Some(name) = field.strip_prefix("N:") {
+        Ok(Symbol::NT(unescape(name)?))
+    } else {
+        Err(format!("Invalid symbol {:?}", field))
+    }
+}
+
+fn expect_line<'a, I>(lines: &mut I, tag: &str) -> Result<Vec<&'a str>, String>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let line = lines.next().ok_or_else(
+        || format!("Unexpected end of file, expected {}", tag),
+    )?;
+    let mut fields = line.split_whitespace();
+    if fields.next() != Some(tag) {
+        return Err(format!("Expected {}, found {:?}", tag, line));
+    }
+    Ok(fields.collect())
+}
+
+impl Parser {
+    /// Serializes the grammar and the compiled table, so that `Parser::load`
+    /// can rebuild the parser without computing the canonical collection.
+    /// The conflicts resolved by default, compression and default reductions
+    /// are kept too; the conflict resolver is not.
+    pub fn save(&self) -> String {
+        let g = self.grammar();
+        let mut out = String::new();
+
+        out.push_str(&format!("lr1 {}\n", FORMAT_VERSION));
+        out.push_str(&format!("fingerprint {:016x}\n", g.fingerprint()));
+        out.push_str(&format!("goal {}\n", write_symbol(&g.goal)));
+        for prod in &g.productions {
+            out.push_str(&format!("prod {} ->", write_symbol(&prod.from)));
+            for s in &prod.to {
+                out.push(' ');
+                out.push_str(&write_symbol(s));
+            }
+            out.push('\n');
+        }
+        self.table().write(&mut out);
+
+        out.push_str(&format!("resolved {}\n", self.resolved_conflicts().len()));
+        for conflict in self.resolved_conflicts() {
+            out.push_str(&format!("conflict {} {}", conflict.state, conflict.lookahead.index()));
+            for &action in &conflict.actions {
+                out.push_str(&format!(" {}", pack(action)));
+            }
+            out.push('\n');
+        }
+
+        out.push_str("options");
+        if self.compressed_table().is_some() {
+            out.push_str(" compressed");
+        }
+        if self.uses_default_reductions() {
+            out.push_str(" default_reductions");
+        }
+        out.push('\n');
+        out.push_str("end\n");
+
+        out
+    }
+
+    /// Loads a parser saved with `Parser::save` for the grammar `g`.
+    ///
+    /// Fails if the file was saved for a different grammar (a stale table),
+    /// by an incompatible version or is malformed. The loaded parser has no
+    /// automaton, so `state_items` returns `None`.
+    pub fn load(g: Grammar, data: &str) -> Result<Parser, String> {
+        let grammar = g.with_fake_goal();
+        let mut lines = data.lines();
+
+        match expect_line(&mut lines, "lr1")?.as_slice() {
+            [version] if *version == FORMAT_VERSION.to_string() => {}
+            fields => {
+                return Err(format!("Unsupported table format version {}", fields.join(" ")))
+            }
+        }
+
+        let fingerprint = match expect_line(&mut lines, "fingerprint")?.as_slice() {
+            [hex] => u64::from_str_radix(hex, 16).map_err(|_| format!("Invalid fingerprint {:?}", hex))?,
+            _ => return Err("Malformed fingerprint".to_string()),
+        };
+        if fingerprint != grammar.fingerprint() {
+            return Err(format!(
+                "Stale table: saved for grammar {:016x}, but the grammar is {:016x}",
+                fingerprint,
+                grammar.fingerprint()
+            ));
+        }
+
+        let goal = match expect_line(&mut lines, "goal")?.as_slice() {
+            [goal] => read_symbol(goal)?,
+            _ => return Err("Malformed goal".to_string()),
+        };
+        let mut prods = Vec::with_capacity(grammar.productions.len());
+        for _ in 0..grammar.productions.len() {
+            let fields = expect_line(&mut lines, "prod")?;
+            if fields.len() < 2 || fields[1] != "->" {
+                return Err(format!("Malformed production {}", fields.join(" ")));
+            }
+            let to = fields[2..].iter().map(|s| read_symbol(s)).collect::<Result<_, _>>()?;
+            prods.push(Arc::new(Production::new(read_symbol(fields[0])?, to)));
+        }
+        if goal != grammar.goal || prods != grammar.productions {
+            return Err("Saved productions do not match the grammar".to_string());
+        }
+
+        let table = Table::read(&mut lines, &grammar)?;
+        let symbols = grammar.symbols();
+
+        let count = match expect_line(&mut lines, "resolved")?.as_slice() {
+            [count] => count.parse::<usize>().map_err(|_| "Malformed resolved count".to_string())?,
+            _ => return Err("Malformed resolved count".to_string()),
+        };
+        let mut resolved = Vec::new();
+        for _ in 0..count {
+            let fields = expect_line(&mut lines, "conflict")?;
+            let numbers = fields
+                .iter()
+                .map(|field| field.parse::<u32>())
+                .collect::<Result<Vec<u32>, _>>()
+                .map_err(|_| format!("Malformed conflict {}", fields.join(" ")))?;
+            if numbers.len() < 4 || numbers[0] as usize >= table.state_count() ||
+                numbers[1] as usize >= symbols.terminal_count() ||
+                numbers[2..].iter().any(|&packed| {
+                    !valid_conflict_action(packed, table.state_count(), grammar.productions.len())
+                })
+            {
+                return Err(format!("Malformed conflict {}", fields.join(" ")));
+            }
+            let actions: Vec<_> = numbers[2..].iter().map(|&packed| unpack(packed)).collect();
+            resolved.push(Conflict::new(
+                numbers[0] as usize,
+                SymbolId(numbers[1]),
+                &actions,
+                BTreeSet::new(),
+            ));
+        }
+
+        let options = expect_line(&mut lines, "options")?;
+        expect_line(&mut lines, "end")?;
+
+        let mut parser = Parser::from_parts(grammar, None, table);
+        parser.set_resolved_conflicts(resolved);
+        for option in options {
+            match option {
+                "compressed" => {
+                    parser.compress();
+                }
+                "default_reductions" => {
+                    parser.use_default_reductions();
+                }
+                _ => return Err(format!("Unknown option {}", option)),
+            }
+        }
+        Ok(parser)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Action, TokenLike};
+    use super::super::test_util::*;
+
+    fn grammar() -> Grammar {
+        let non_terminals = vec!["S", "L"];
+        let prods = vec![
+            ("S", vec!["(", "L", ")"]),
+            ("S", vec!["a b"]),
+            ("L", vec!["L", ",", "S"]),
+            ("L", vec!["S"]),
+        ];
+
+        Grammar::from_str("S", non_terminals, prods)
+    }
+
+    #[test]
+    fn save_load_test() {
+        let parser = Parser::new(grammar());
+        let data = parser.save();
+        let loaded = Parser::load(grammar(), &data).unwrap();
+
+        assert_eq!(loaded.table(), parser.table());
+        assert!(loaded.automaton().is_none());
+        assert_eq!(loaded.save(), data);
+
+        let tokens: Vec<Box<dyn TokenLike>> = vec!["(", "a b", ",", "a b", ")", "EOF"]
+            .into_iter()
+            .map(token)
+            .collect();
+        assert!(loaded.parse(tokens.into_iter()).is_ok());
+    }
+
+    // The dangling else, resolved by default as expected.
+    fn dangling_else() -> Grammar {
+        let mut g = dangling_else_grammar();
+        g.expect_conflicts(1, 0);
+        g
+    }
+
+    #[test]
+    fn save_load_state_test() {
+        let mut parser = Parser::new(dangling_else());
+        parser.compress();
+        parser.use_default_reductions();
+        let data = parser.save();
+        let loaded = Parser::load(dangling_else(), &data).unwrap();
+
+        assert_eq!(loaded.resolved_conflicts().len(), 1);
+        assert_eq!(loaded.warnings(), parser.warnings());
+        assert_eq!(loaded.compressed_table().unwrap().report(), parser.compressed_table().unwrap().report());
+        assert!(loaded.uses_default_reductions());
+        for state in 0..parser.table().state_count() {
+            assert_eq!(loaded.default_reduction(state), parser.default_reduction(state));
+        }
+        assert_eq!(loaded.save(), data);
+    }
+
+    #[test]
+    fn stale_table_test() {
+        let data = Parser::new(grammar()).save();
+        let changed = Grammar::from_str(
+            "S",
+            vec!["S"],
+            vec![("S", vec!["(", "S", ")"]), ("S", vec!["a b"])],
+        );
+
+        let err = Parser::load(changed, &data).unwrap_err();
+        assert!(err.starts_with("Stale table"), "{}", err);
+    }
+
+    #[test]
+    fn malformed_table_test() {
+        let data = Parser::new(grammar()).save();
+
+        let old_version = data.replacen("lr1 2", "lr1 1", 1);
+        assert!(Parser::load(grammar(), &old_version).is_err());
+
+        let truncated: String = data.lines().take(8).collect::<Vec<_>>().join("\n");
+        assert!(Parser::load(grammar(), &truncated).is_err());
+
+        // Point a goto past the last state.
+        let mut lines: Vec<String> = data.lines().map(|line| line.to_string()).collect();
+        let row = lines.iter().position(|line| line.starts_with("g ")).unwrap();
+        let mut cells: Vec<&str> = lines[row].split(' ').collect();
+        cells[1] = "999";
+        lines[row] = cells.join(" ");
+        let bad_goto = lines.join("\n");
+        assert!(Parser::load(grammar(), &bad_goto).is_err());
+
+        // Sizes that do not match the grammar fail before any row is read.
+        let header = data.lines().position(|line| line.starts_with("table ")).unwrap();
+        let sizes = |sizes: &str| {
+            let mut lines: Vec<&str> = data.lines().collect();
+            lines[header] = sizes;
+            Parser::load(grammar(), &lines.join("\n")).err().unwrap()
+        };
+        let g = grammar().with_fake_goal();
+        let (terminals, non_terminals) = (g.symbols().terminal_count(), g.symbols().non_terminal_count());
+        let err = sizes(&format!("table 3 {} 1000000000", terminals));
+        assert_eq!(err, "Table size does not match the grammar");
+        let err = sizes(&format!("table 1000000000 {} {}", terminals, non_terminals));
+        assert!(err.starts_with("Expected a"), "{}", err);
+        let err = sizes(&format!("table 0 {} {}", terminals, non_terminals));
+        assert!(err.starts_with("Invalid state count"), "{}", err);
+
+        // Resolve a conflict with a reduction past the last production.
+        let data = Parser::new(dangling_else()).save();
+        let mut lines: Vec<String> = data.lines().map(|line| line.to_string()).collect();
+        let row = lines.iter().position(|line| line.starts_with("conflict ")).unwrap();
+        let mut cells: Vec<String> = lines[row].split(' ').map(|cell| cell.to_string()).collect();
+        cells[3] = pack(Action::Reduce(999)).to_string();
+        lines[row] = cells.join(" ");
+        let bad_reduce = lines.join("\n");
+        let err = Parser::load(dangling_else(), &bad_reduce).err().unwrap();
+        assert!(err.starts_with("Malformed conflict"), "{}", err);
+    }
+}
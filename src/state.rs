@@ -0,0 +1,50 @@
+use std::collections::BTreeMap;
+
+use super::{BitSet, SymbolId};
+
+/// An LR(1) item core (production index and stacktop) together with every
+/// lookahead it is valid for, stored as a set of terminal indices.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LrItem {
+    pub prod: usize,
+    pub stacktop: usize,
+    pub lookaheads: BitSet,
+}
+
+impl LrItem {
+    pub fn new(prod: usize, stacktop: usize, lookaheads: BitSet) -> LrItem {
+        LrItem {
+            prod,
+            stacktop,
+            lookaheads,
+        }
+    }
+
+    pub fn core(&self) -> (usize, usize) {
+        (self.prod, self.stacktop)
+    }
+}
+
+/// A state of the canonical collection.
+///
+/// States are identified by their kernel items only (sorted, so equal kernels
+/// compare equal); the closure is recomputed from the kernel when needed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct State {
+    pub kernel: Vec<LrItem>,
+    /// Successor state for every symbol that can follow the stacktop.
+    pub transitions: BTreeMap<SymbolId, usize>,
+    /// Complete items of the closure: production index and lookaheads.
+    pub reductions: Vec<(usize, BitSet)>,
+}
+
+impl State {
+    pub fn new(mut kernel: Vec<LrItem>) -> State {
+        kernel.sort();
+        State {
+            kernel,
+            transitions: BTreeMap::new(),
+            reductions: Vec::new(),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::{BTreeSet, HashMap};
 use std::fmt;
 use super::{EOF, LAMBDA};
 
@@ -25,30 +26,22 @@ impl Symbol {
     }
 
     pub fn is_terminal(&self) -> bool {
-        match self {
-            &Symbol::T(_) => true,
-            _ => false,
-        }
+        matches!(*self, Symbol::T(_))
     }
 
     pub fn is_non_terminal(&self) -> bool {
-        match self {
-            &Symbol::NT(_) => true,
-            _ => false,
-        }
+        matches!(*self, Symbol::NT(_))
     }
 
     pub fn to_string(&self) -> &String {
-        match self {
-            &Symbol::T(ref s) => s,
-            &Symbol::NT(ref s) => s,
+        match *self {
+            Symbol::T(ref s) | Symbol::NT(ref s) => s,
         }
     }
 
     pub fn as_str(&self) -> &str {
-        match self {
-            &Symbol::T(ref s) => s,
-            &Symbol::NT(ref s) => s,
+        match *self {
+            Symbol::T(ref s) | Symbol::NT(ref s) => s,
         }
     }
 }
@@ -58,3 +51,110 @@ impl fmt::Display for Symbol {
         write!(f, "{:?}", self)
     }
 }
+
+/// Integer handle of a `Symbol` interned in a `SymbolTable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SymbolId(pub u32);
+
+impl SymbolId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Interned symbols of a grammar.
+///
+/// Terminals come first, starting with `EOF` at id 0, followed by the non
+/// terminals, so a terminal id can be used directly as a column index and a
+/// non terminal id as `id - terminal_count()`.
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    ids: HashMap<Symbol, SymbolId>,
+    terminal_ids: HashMap<String, SymbolId>,
+    terminal_count: usize,
+}
+
+impl SymbolTable {
+    pub fn new(symbols: &BTreeSet<Symbol>) -> SymbolTable {
+        let eof = Symbol::eof();
+        let mut all = vec![eof.clone()];
+        all.extend(
+            symbols
+                .iter()
+                .filter(|s| s.is_terminal() && **s != eof)
+                .cloned(),
+        );
+        let terminal_count = all.len();
+        all.extend(symbols.iter().filter(|s| s.is_non_terminal()).cloned());
+
+        let ids = all.iter()
+            .enumerate()
+            .map(|(i, s)| (s.clone(), SymbolId(i as u32)))
+            .collect();
+        let terminal_ids = all[..terminal_count]
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.as_str().to_string(), SymbolId(i as u32)))
+            .collect();
+
+        SymbolTable {
+            symbols: all,
+            ids,
+            terminal_ids,
+            terminal_count,
+        }
+    }
+
+    pub fn eof() -> SymbolId {
+        SymbolId(0)
+    }
+
+    pub fn id(&self, symbol: &Symbol) -> Option<SymbolId> {
+        self.ids.get(symbol).cloned()
+    }
+
+    /// Looks up a terminal by its name, e.g. a token kind.
+    pub fn terminal_id(&self, kind: &str) -> Option<SymbolId> {
+        self.terminal_ids.get(kind).cloned()
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &Symbol {
+        &self.symbols[id.index()]
+    }
+
+    pub fn is_terminal(&self, id: SymbolId) -> bool {
+        id.index() < self.terminal_count
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn terminal_count(&self) -> usize {
+        self.terminal_count
+    }
+
+    pub fn non_terminal_count(&self) -> usize {
+        self.symbols.len() - self.terminal_count
+    }
+
+    pub fn non_terminal_index(&self, id: SymbolId) -> usize {
+        assert!(!self.is_terminal(id), "Expected a non terminal");
+        id.index() - self.terminal_count
+    }
+
+    pub fn terminals(&self) -> Vec<SymbolId> {
+        (0..self.terminal_count).map(|i| SymbolId(i as u32)).collect()
+    }
+
+    pub fn non_terminals(&self) -> Vec<SymbolId> {
+        (self.terminal_count..self.symbols.len())
+            .map(|i| SymbolId(i as u32))
+            .collect()
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::BTreeMap;
+
+use super::{Action, Automaton, Grammar, SymbolId};
+use super::precedence::resolve;
+
+// Action cells are packed into a u32: the two low bits are a tag and the rest
+// is the payload (state, production or conflict index). Tag 0 is either an
+// error (payload 0) or Accept (payload 1).
+pub(crate) const TAG_BITS: u32 = 2;
+pub(crate) const TAG_MASK: u32 = (1 << TAG_BITS) - 1;
+pub(crate) const ERROR: u32 = 0;
+const ACCEPT: u32 = 1 << TAG_BITS;
+const SHIFT: u32 = 1;
+pub(crate) const REDUCE: u32 = 2;
+pub(crate) const CONFLICT: u32 = 3;
+
+pub(crate) const NO_STATE: u32 = u32::MAX;
+
+/// Contents of a cell of the action table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionEntry<'a> {
+    Error,
+    Action(Action),
+    /// More than one action, sorted.
+    Conflict(&'a [Action]),
+}
+
+/// Compiled LR(1) tables: dense arrays indexed by state number and by
+/// terminal id (action) or non terminal index (goto).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    state_count: usize,
+    terminal_count: usize,
+    non_terminal_count: usize,
+    action: Vec<u32>,
+    goto: Vec<u32>,
+    conflicts: Vec<Vec<Action>>,
+}
+
+pub(crate) fn pack(action: Action) -> u32 {
+    match action {
+        Action::Accept => ACCEPT,
+        Action::Shift(state) => (state as u32) << TAG_BITS | SHIFT,
+        Action::Reduce(prod) => (prod as u32) << TAG_BITS | REDUCE,
+    }
+}
+
+pub(crate) fn unpack(packed: u32) -> Action {
+    let payload = (packed >> TAG_BITS) as usize;
+    match packed & TAG_MASK {
+        SHIFT => Action::Shift(payload),
+        REDUCE => Action::Reduce(payload),
+        _ => Action::Accept,
+    }
+}
+
+pub(crate) fn entry(packed: u32, conflicts: &[Vec<Action>]) -> ActionEntry<'_> {
+    if packed == ERROR {
+        ActionEntry::Error
+    } else if packed & TAG_MASK == CONFLICT {
+        ActionEntry::Conflict(&conflicts[(packed >> TAG_BITS) as usize])
+    } else {
+        ActionEntry::Action(unpack(packed))
+    }
+}
+
+// Whether `packed` is a cell of a table with `state_count` states, for a
+// grammar with `prod_count` productions, and refers to one of its
+// `conflict_count` conflicts if it is a conflict.
+fn valid_cell(packed: u32, state_count: usize, prod_count: usize, conflict_count: usize) -> bool {
+    let payload = (packed >> TAG_BITS) as usize;
+    match packed & TAG_MASK {
+        SHIFT => payload < state_count,
+        REDUCE => payload > 0 && payload < prod_count,
+        CONFLICT => payload < conflict_count,
+        _ => packed == ERROR || packed == ACCEPT,
+    }
+}
+
+// Whether `packed` can be one of the actions of a conflict.
+pub(crate) fn valid_conflict_action(packed: u32, state_count: usize, prod_count: usize) -> bool {
+    packed != ERROR && valid_cell(packed, state_count, prod_count, 0)
+}
+
+impl Table {
+    /// Compiles the tables of an automaton built from `g`, resolving
+    /// shift/reduce conflicts with the grammar's precedences.
+    pub fn new(automaton: &Automaton, g: &Grammar) -> Table {
+        let symbols = g.symbols();
+        let mut table = Table::empty(
+            automaton.states.len(),
+            symbols.terminal_count(),
+            symbols.non_terminal_count(),
+        );
+
+        for (i, state) in automaton.states.iter().enumerate() {
+            let mut cells: BTreeMap<SymbolId, Vec<Action>> = BTreeMap::new();
+            for (&x, &next) in &state.transitions {
+                if symbols.is_terminal(x) {
+                    cells.entry(x).or_default().push(Action::Shift(next));
+                } else {
+                    table.set_goto(i, symbols.non_terminal_index(x), next);
+                }
+            }
+
+            for &(prod, ref lookaheads) in &state.reductions {
+                let action = if prod == 0 {
+                    Action::Accept
+                } else {
+                    Action::Reduce(prod)
+                };
+                for la in lookaheads.iter() {
+                    cells.entry(SymbolId(la as u32)).or_default().push(action);
+                }
+            }
+
+            for (t, actions) in cells {
+                let actions = if actions.len() > 1 {
+                    resolve(g, t, &actions)
+                } else {
+                    actions
+                };
+                for action in actions {
+                    table.add_action(i, t, action);
+                }
+            }
+        }
+
+        table
+    }
+
+    /// A table where every action is an error and every goto is missing.
+    pub fn empty(state_count: usize, terminal_count: usize, non_terminal_count: usize) -> Table {
+        Table {
+            state_count,
+            terminal_count,
+            non_terminal_count,
+            action: vec![ERROR; state_count * terminal_count],
+            goto: vec![NO_STATE; state_count * non_terminal_count],
+            conflicts: Vec::new(),
+        }
+    }
+
+    pub fn state_count(&self) -> usize {
+        self.state_count
+    }
+
+    pub fn terminal_count(&self) -> usize {
+        self.terminal_count
+    }
+
+    pub fn non_terminal_count(&self) -> usize {
+        self.non_terminal_count
+    }
+
+    /// Adds an action to a cell, turning it into a conflict if it already
+    /// holds a different one.
+    pub fn add_action(&mut self, state: usize, terminal: SymbolId, action: Action) {
+        let cell = state * self.terminal_count + terminal.index();
+        let packed = self.action[cell];
+
+        if packed == ERROR {
+            self.action[cell] = pack(action);
+        } else if packed & TAG_MASK == CONFLICT {
+            let actions = &mut self.conflicts[(packed >> TAG_BITS) as usize];
+            if let Err(pos) = actions.binary_search(&action) {
+                actions.insert(pos, action);
+            }
+        } else if packed != pack(action) {
+            let mut actions = vec![unpack(packed), action];
+            actions.sort();
+            self.action[cell] = (self.conflicts.len() as u32) << TAG_BITS | CONFLICT;
+            self.conflicts.push(actions);
+        }
+    }
+
+    /// Replaces the contents of a cell. A conflicting cell keeps its slot in
+    /// the conflict list while it still conflicts, and frees it otherwise.
+    pub fn set_actions(&mut self, state: usize, terminal: SymbolId, actions: &[Action]) {
+        let cell = state * self.terminal_count + terminal.index();
+        let packed = self.action[cell];
+        let mut actions = actions.to_vec();
+        actions.sort();
+        actions.dedup();
+
+        if packed & TAG_MASK == CONFLICT && self.action.iter().filter(|&&p| p == packed).count() == 1 {
+            let slot = (packed >> TAG_BITS) as usize;
+            if actions.len() > 1 {
+                self.conflicts[slot] = actions;
+                return;
+            }
+            self.remove_conflict(slot);
+        }
+
+        self.action[cell] = ERROR;
+        for action in actions {
+            self.add_action(state, terminal, action);
+        }
+    }
+
+    // Drops conflict `slot`, whose only cell is being replaced, and moves the
+    // last conflict into the freed slot.
+    fn remove_conflict(&mut self, slot: usize) {
+        let last = self.conflicts.len() - 1;
+        self.conflicts.swap_remove(slot);
+        if slot != last {
+            let moved = (last as u32) << TAG_BITS | CONFLICT;
+            for packed in self.action.iter_mut().filter(|p| **p == moved) {
+                *packed = (slot as u32) << TAG_BITS | CONFLICT;
+            }
+        }
+    }
+
+    pub fn set_goto(&mut self, state: usize, non_terminal: usize, next: usize) {
+        self.goto[state * self.non_terminal_count + non_terminal] = next as u32;
+    }
+
+    pub fn action(&self, state: usize, terminal: SymbolId) -> ActionEntry<'_> {
+        entry(self.packed_action(state, terminal), &self.conflicts)
+    }
+
+    pub(crate) fn packed_action(&self, state: usize, terminal: SymbolId) -> u32 {
+        self.action[state * self.terminal_count + terminal.index()]
+    }
+
+    pub(crate) fn conflicts(&self) -> &[Vec<Action>] {
+        &self.conflicts
+    }
+
+    /// Every action of a cell, empty on error.
+    pub fn actions(&self, state: usize, terminal: SymbolId) -> Vec<Action> {
+        match self.action(state, terminal) {
+            ActionEntry::Error => vec![],
+            ActionEntry::Action(action) => vec![action],
+            ActionEntry::Conflict(actions) => actions.to_vec(),
+        }
+    }
+
+    /// For every state, the production it reduces if reducing it is the
+    /// only action of the state, whatever the lookahead.
+    pub fn default_reductions(&self) -> Vec<Option<usize>> {
+        (0..self.state_count)
+            .map(|state| {
+                let row = &self.action[state * self.terminal_count..(state + 1) * self.terminal_count];
+                let mut cells = row.iter().cloned().filter(|&packed| packed != ERROR);
+                match cells.next() {
+                    Some(first) if first & TAG_MASK == REDUCE && cells.all(|packed| packed == first) => {
+                        Some((first >> TAG_BITS) as usize)
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Goto by non terminal index (`SymbolTable::non_terminal_index`).
+    pub fn goto(&self, state: usize, non_terminal: usize) -> Option<usize> {
+        match self.goto[state * self.non_terminal_count + non_terminal] {
+            NO_STATE => None,
+            next => Some(next as usize),
+        }
+    }
+
+    pub fn has_conflicts(&self) -> bool {
+        self.action.iter().any(|packed| packed & TAG_MASK == CONFLICT)
+    }
+
+    /// Appends the table in the line based text format read by `Table::read`.
+    pub(crate) fn write(&self, out: &mut String) {
+        let join = |cells: &[u32]| {
+            cells
+                .iter()
+                .map(|&cell| if cell == NO_STATE {
+                    "-".to_string()
+                } else {
+                    cell.to_string()
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+
+        out.push_str(&format!(
+            "table {} {} {}\n",
+            self.state_count,
+            self.terminal_count,
+            self.non_terminal_count
+        ));
+        for row in self.action.chunks(self.terminal_count.max(1)) {
+            out.push_str(&format!("a {}\n", join(row)));
+        }
+        for row in self.goto.chunks(self.non_terminal_count.max(1)) {
+            out.push_str(&format!("g {}\n", join(row)));
+        }
+        out.push_str(&format!("conflicts {}\n", self.conflicts.len()));
+        for actions in &self.conflicts {
+            let packed: Vec<u32> = actions.iter().map(|&action| pack(action)).collect();
+            out.push_str(&format!("c {}\n", join(&packed)));
+        }
+    }
+
+    /// Reads a table for `g` written by `Table::write`, checking its sizes
+    /// against the grammar and that every cell refers to an existing state,
+    /// production or conflict.
+    pub(crate) fn read<'a, I>(lines: &mut I, g: &Grammar) -> Result<Table, String>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let symbols = g.symbols();
+        let terminal_count = symbols.terminal_count();
+        let non_terminal_count = symbols.non_terminal_count();
+        let prod_count = g.productions.len();
+
+        let state_count = match *read_line(lines, "table")?.as_slice() {
+            [states, terminals, non_terminals]
+                if terminals as usize == terminal_count && non_terminals as usize == non_terminal_count => {
+                states as usize
+            }
+            [_, _, _] => return Err("Table size does not match the grammar".to_string()),
+            _ => return Err("Malformed table sizes".to_string()),
+        };
+        // Shifts pack the state above the tag, and the rows are only
+        // allocated as they are read, so a bad count fails on a missing row.
+        if state_count == 0 || state_count > (u32::MAX >> TAG_BITS) as usize {
+            return Err(format!("Invalid state count {}", state_count));
+        }
+
+        let mut action = Vec::new();
+        for _ in 0..state_count {
+            action.extend(read_row(lines, "a", terminal_count)?);
+        }
+        let mut goto = Vec::new();
+        for _ in 0..state_count {
+            goto.extend(read_row(lines, "g", non_terminal_count)?);
+        }
+
+        let conflict_count = match read_line(lines, "conflicts")?.as_slice() {
+            [count] => *count as usize,
+            _ => return Err("Malformed conflict count".to_string()),
+        };
+        let mut conflicts = Vec::new();
+        for _ in 0..conflict_count {
+            let packed = read_line(lines, "c")?;
+            if packed.len() < 2 ||
+                packed.iter().any(|&p| !valid_conflict_action(p, state_count, prod_count))
+            {
+                return Err("Invalid conflict entry".to_string());
+            }
+            conflicts.push(packed.into_iter().map(unpack).collect::<Vec<Action>>());
+        }
+
+        if let Some(&bad) = action
+            .iter()
+            .find(|&&p| !valid_cell(p, state_count, prod_count, conflict_count))
+        {
+            return Err(format!("Invalid action cell {}", bad));
+        }
+        if goto.iter().any(|&next| next != NO_STATE && next as usize >= state_count) {
+            return Err("Invalid goto cell".to_string());
+        }
+
+        Ok(Table {
+            state_count,
+            terminal_count,
+            non_terminal_count,
+            action,
+            goto,
+            conflicts,
+        })
+    }
+}
+
+fn read_line<'a, I>(lines: &mut I, tag: &str) -> Result<Vec<u32>, String>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let line = lines.next().ok_or_else(
+        || format!("Unexpected end of table, expected {}", tag),
+    )?;
+    let mut fields = line.split_whitespace();
+    if fields.next() != Some(tag) {
+        return Err(format!("Expected {}, found {:?}", tag, line));
+    }
+
+    fields
+        .map(|field| if field == "-" {
+            Ok(NO_STATE)
+        } else {
+            field.parse().map_err(|_| format!("Invalid number {:?}", field))
+        })
+        .collect()
+}
+
+fn read_row<'a, I>(lines: &mut I, tag: &str, len: usize) -> Result<Vec<u32>, String>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let row = read_line(lines, tag)?;
+    if row.len() != len {
+        return Err(format!("Expected {} cells in {} row, found {}", len, tag, row.len()));
+    }
+
+    Ok(row)
+}
@@ -0,0 +1,62 @@
+//! Lexers and grammars shared by the unit tests.
+
+use super::{Grammar, TokenLike, LAMBDA};
+
+/// A token whose lexeme is its kind.
+pub fn token(kind: &str) -> Box<dyn TokenLike> {
+    Box::new((kind.to_string(), kind.to_string()))
+}
+
+/// The whitespace separated token kinds of `s`.
+pub fn tokens(s: &str) -> Vec<Box<dyn TokenLike>> {
+    s.split_whitespace().map(token).collect()
+}
+
+/// `tokens`, as the iterator the parsers take.
+pub fn lex(s: &str) -> Box<dyn Iterator<Item = Box<dyn TokenLike>>> {
+    Box::new(tokens(s).into_iter())
+}
+
+/// Lists of nested pairs of parentheses: `( ( ) ) ( )`.
+pub fn list_grammar() -> Grammar {
+    let non_terminals = vec!["List", "Pair"];
+    let prods = vec![
+        ("List", vec!["List", "Pair"]),
+        ("List", vec!["Pair"]),
+        ("Pair", vec!["(", "Pair", ")"]),
+        ("Pair", vec!["(", ")"]),
+    ];
+
+    Grammar::from_str("List", non_terminals, prods)
+}
+
+/// Balanced parentheses, the empty string included.
+pub fn balanced_grammar() -> Grammar {
+    Grammar::from_str(
+        "S",
+        vec!["S"],
+        vec![("S", vec!["(", "S", ")", "S"]), ("S", vec![LAMBDA])],
+    )
+}
+
+/// Sums without precedence: ambiguous, with a shift/reduce conflict on `+`.
+pub fn sum_grammar() -> Grammar {
+    Grammar::from_str(
+        "E",
+        vec!["E"],
+        vec![("E", vec!["E", "+", "E"]), ("E", vec!["num"])],
+    )
+}
+
+/// The dangling else, with a shift/reduce conflict on `else`.
+pub fn dangling_else_grammar() -> Grammar {
+    Grammar::from_str(
+        "S",
+        vec!["S"],
+        vec![
+            ("S", vec!["if", "c", "then", "S"]),
+            ("S", vec!["if", "c", "then", "S", "else", "S"]),
+            ("S", vec!["x"]),
+        ],
+    )
+}
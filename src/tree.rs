@@ -5,7 +5,7 @@ pub type NodeId = usize;
 
 pub enum NodeData {
     NT(Symbol),
-    Token(Box<TokenLike>),
+    Token(Box<dyn TokenLike>),
 }
 
 impl From<Symbol> for NodeData {
@@ -15,17 +15,17 @@ impl From<Symbol> for NodeData {
     }
 }
 
-impl From<Box<TokenLike>> for NodeData {
-    fn from(token: Box<TokenLike>) -> Self {
+impl From<Box<dyn TokenLike>> for NodeData {
+    fn from(token: Box<dyn TokenLike>) -> Self {
         NodeData::Token(token)
     }
 }
 
 impl fmt::Display for NodeData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            &NodeData::Token(ref token) => write!(f, "{} {}", token.kind(), token.lexeme()),
-            &NodeData::NT(ref symbol) => write!(f, "{}", symbol),
+        match *self {
+            NodeData::Token(ref token) => write!(f, "{} {}", token.kind(), token.lexeme()),
+            NodeData::NT(ref symbol) => write!(f, "{}", symbol),
         }
 
     }
@@ -41,7 +41,7 @@ pub struct Node {
 impl Node {
     pub fn new<T: Into<NodeData>>(id: NodeId, data: T) -> Node {
         Node {
-            id: id,
+            id,
             parent: None,
             children: vec![],
             data: data.into(),
@@ -57,6 +57,7 @@ impl fmt::Display for Node {
 }
 
 
+#[derive(Default)]
 pub struct Tree {
     nodes: Vec<Node>,
     root: Option<NodeId>,
@@ -95,7 +96,37 @@ impl Tree {
         parent.children.push(child_id);
     }
 
-    pub fn preorder_walk(&self, x: NodeId, level: usize) {
+    pub fn root(&self) -> Option<NodeId> {
+        self.root
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Removes the nodes from `len` on, detaching their children, so the
+    /// tree is as it was when it had `len` nodes.
+    pub fn truncate(&mut self, len: usize) {
+        self.nodes.truncate(len);
+        for node in &mut self.nodes {
+            if node.parent.is_some_and(|parent| parent >= len) {
+                node.parent = None;
+            }
+        }
+        if self.root.is_some_and(|root| root >= len) {
+            self.root = None;
+        }
+    }
+
+    fn write_node(&self, f: &mut fmt::Formatter, x: NodeId, level: usize) -> fmt::Result {
         if let Some(x) = self.nodes.get(x) {
             let separator = "|-- ";
             let s = format!("{}{}", separator, x);
@@ -103,21 +134,39 @@ impl Tree {
 
             let mut space = String::new();
             for _ in 0..space_n {
-                space.push_str(" ");
+                space.push(' ');
             }
-            println!("{}{}", space, s);
+            writeln!(f, "{}{}", space, s)?;
 
             for c in &x.children {
-                self.preorder_walk(*c, level + 1);
+                self.write_node(f, *c, level + 1)?;
             }
         }
+
+        Ok(())
     }
 
-    pub fn print(&self) {
-        if self.root == None {
-            return;
+    pub fn preorder_walk(&self, x: NodeId, level: usize) {
+        struct Walk<'a>(&'a Tree, NodeId, usize);
+        impl<'a> fmt::Display for Walk<'a> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0.write_node(f, self.1, self.2)
+            }
         }
 
-        self.preorder_walk(self.root.unwrap(), 0);
+        print!("{}", Walk(self, x, level));
+    }
+
+    pub fn print(&self) {
+        print!("{}", self);
+    }
+}
+
+impl fmt::Display for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.root {
+            Some(root) => self.write_node(f, root, 0),
+            None => Ok(()),
+        }
     }
 }